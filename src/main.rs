@@ -2,19 +2,36 @@
 #![allow(non_snake_case)]
 
 use std::cell::{OnceCell, RefCell};
+use std::ffi::c_void;
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 use objc2::rc::Retained;
 use objc2::runtime::{AnyObject, Bool, ProtocolObject};
 use objc2::AnyThread;
-use objc2::{define_class, msg_send, sel, DefinedClass, MainThreadMarker, MainThreadOnly};
+use objc2::{
+    define_class, msg_send, sel, ClassType, DefinedClass, MainThreadMarker, MainThreadOnly, Message,
+};
 use objc2_app_kit::{
-    NSApplication, NSApplicationActivationPolicy, NSApplicationDelegate, NSAutoresizingMaskOptions,
-    NSBackingStoreType, NSBezelStyle, NSBitmapImageRep, NSButton, NSEvent, NSImage, NSImageScaling,
-    NSImageView, NSMagnificationGestureRecognizer, NSScrollView, NSSlider, NSWindow,
-    NSWindowDelegate, NSWindowStyleMask,
+    NSAlert, NSAnimatablePropertyContainer, NSApplication, NSApplicationActivationPolicy,
+    NSApplicationDelegate, NSAutoresizingMaskOptions, NSBackingStoreType, NSBitmapImageFileType,
+    NSBitmapImageRep, NSButton, NSColor, NSColorSpace, NSColorWell, NSControlStateValueOff,
+    NSControlStateValueOn, NSDragOperation, NSEvent, NSEventModifierFlags, NSFont, NSImage,
+    NSImageFrameStyle, NSImageScaling, NSImageView, NSMagnificationGestureRecognizer, NSMenu,
+    NSMenuItem, NSPasteboard, NSPasteboardTypeFileURL, NSPasteboardTypePDF, NSPasteboardTypeString,
+    NSPopUpButton, NSPrintOperation, NSPrintingPaginationMode, NSResponder, NSSavePanel,
+    NSScrollView, NSSlider, NSTabView, NSTabViewItem, NSTextField, NSToolbar, NSToolbarDelegate,
+    NSToolbarDisplayMode, NSToolbarFlexibleSpaceItemIdentifier, NSToolbarItem,
+    NSToolbarSpaceItemIdentifier, NSView, NSWindow, NSWindowDelegate, NSWindowStyleMask,
+    NSWorkspace,
 };
 use objc2_foundation::{
-    ns_string, NSArray, NSNotification, NSObject, NSObjectProtocol, NSPoint, NSRect, NSSize, NSURL,
+    ns_string, NSArray, NSCopying, NSData, NSDate, NSDateFormatter, NSDictionary, NSHomeDirectory,
+    NSLocale, NSNotification, NSNumber, NSObject, NSObjectProtocol, NSPoint, NSRect, NSSize,
+    NSString, NSTimer, NSUserDefaults, NSURL,
 };
 use objc2_uniform_type_identifiers::UTType;
 
@@ -23,9 +40,10 @@ use objc2_uniform_type_identifiers::UTType;
 //------------------------------------------------------------------------------
 /// A simple 5x5 pixel bitmap font for rendering text in the image viewer
 /// Each character is represented as a 5x5 grid of binary pixels (0 = transparent, 1 = filled)
-/// The array contains 30 characters in the following order:
-/// C, O, M, I, N, G, S, P, J, 2, (space), F, L, E, D, T, A, R, B, 0-9, -, .
-const BITMAP_CHARS: [[[u8; 5]; 5]; 30] = [
+/// The array contains 72 glyphs in the following order:
+/// C, O, M, I, N, G, S, P, J, 2, (space), F, L, E, D, T, A, R, B, 0-9, -, .,
+/// H, K, Q, U, V, W, X, Y, Z, a-z, comma, colon, /, (, ), _, missing glyph box
+const BITMAP_CHARS: [[[u8; 5]; 5]; 72] = [
     // 0: C
     [
         [0, 1, 1, 1, 0],
@@ -266,704 +284,5500 @@ const BITMAP_CHARS: [[[u8; 5]; 5]; 30] = [
         [0, 0, 0, 0, 0],
         [0, 0, 1, 0, 0],
     ],
-];
-
-/// Mapping from characters to their index in the BITMAP_CHARS array
-/// Unknown characters will map to index 10 (space) as a fallback
-const CHAR_INDICES: [(char, usize); 30] = [
-    ('C', 0),
-    ('O', 1),
-    ('M', 2),
-    ('I', 3),
-    ('N', 4),
-    ('G', 5),
-    ('S', 6),
-    ('P', 7),
-    ('J', 8),
-    ('2', 9),
-    (' ', 10),
-    ('F', 11),
-    ('L', 12),
-    ('E', 13),
-    ('D', 14),
-    ('T', 15),
-    ('A', 16),
-    ('R', 17),
-    ('B', 18),
-    ('0', 19),
-    ('1', 20),
-    ('3', 21),
-    ('4', 22),
-    ('5', 23),
-    ('6', 24),
-    ('7', 25),
-    ('8', 26),
-    ('9', 27),
-    ('-', 28),
-    ('.', 29),
-];
-
-// Structure to hold source pattern and debug pixel data
-#[derive(Debug)]
-struct SourcePattern {
-    buffer: Vec<u8>,
-    width: usize,
-    height: usize,
-    bytes_per_row: usize,
-}
-
-// Enum to represent different pattern types
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum PatternType {
-    Checkerboard,
-    Gradient,
-    Text,
-}
-
-// Custom image view that forwards mouse events to our app delegate
-define_class!(
-    #[unsafe(super = NSImageView)]
-    #[thread_kind = MainThreadOnly]
-    #[name = "CustomImageView"]
-    #[derive(Debug)]
-    struct CustomImageView;
-
-    unsafe impl NSObjectProtocol for CustomImageView {}
-
-    impl CustomImageView {
-        #[unsafe(method(mouseDown:))]
-        fn mouseDown(&self, event: &NSEvent) {
-            if let Some(delegate) = self.get_app_delegate() {
-                unsafe {
-                    let _: Bool = msg_send![delegate, mouseDown: event];
-                }
-            }
-
-            unsafe {
-                let _: () = msg_send![super(self), mouseDown: event];
-            }
-        }
-
-        #[unsafe(method(mouseDragged:))]
-        fn mouseDragged(&self, event: &NSEvent) {
-            if let Some(delegate) = self.get_app_delegate() {
-                unsafe {
-                    let _: Bool = msg_send![delegate, mouseDragged: event];
-                }
-            }
-
-            unsafe {
-                let _: () = msg_send![super(self), mouseDragged: event];
-            }
-        }
-
-        #[unsafe(method(mouseUp:))]
-        fn mouseUp(&self, event: &NSEvent) {
-            if let Some(delegate) = self.get_app_delegate() {
-                unsafe {
-                    let _: Bool = msg_send![delegate, mouseUp: event];
-                }
-            }
-
-            unsafe {
-                let _: () = msg_send![super(self), mouseUp: event];
-            }
-        }
-    }
-);
-
-impl CustomImageView {
-    fn new(mtm: MainThreadMarker, frame: NSRect) -> Retained<Self> {
-        let this = Self::alloc(mtm);
-        unsafe {
-            let obj: Retained<Self> = msg_send![this, initWithFrame: frame];
-            obj
-        }
-    }
-
-    fn get_app_delegate(&self) -> Option<&AnyObject> {
-        let mtm = self.mtm();
-        let app = NSApplication::sharedApplication(mtm);
-
-        unsafe {
-            let delegate: *const AnyObject = msg_send![&*app, delegate];
-            if delegate.is_null() {
-                None
-            } else {
-                Some(&*delegate)
-            }
-        }
-    }
-}
-
-// Add the CachedSourcePattern struct
-#[derive(Debug)]
-struct CachedSourcePattern {
-    pattern: SourcePattern,
-    pattern_type: PatternType,
-    primary_text: Option<String>,
-    secondary_text: Option<String>,
-    source_width: usize,
-    source_height: usize,
-}
-
-// Define the app delegate with ivars
-#[derive(Debug, Default)]
-struct AppDelegateIvars {
-    window: OnceCell<Retained<NSWindow>>,
-    scroll_view: OnceCell<Retained<NSScrollView>>,
-    image_view: OnceCell<Retained<CustomImageView>>,
-    selected_file_path: RefCell<Option<Retained<NSURL>>>,
-    decoded_image: RefCell<Option<Retained<NSImage>>>,
-    zoom_slider: OnceCell<Retained<NSSlider>>,
-    last_mouse_location: RefCell<NSPoint>,
-    is_panning: RefCell<bool>,
-    magnification_recognizer: OnceCell<Retained<NSMagnificationGestureRecognizer>>,
-    base_zoom_level: RefCell<f64>,
-    state: RefCell<AppState>,
-    cached_pattern: RefCell<Option<CachedSourcePattern>>,
-}
-
-// State container for state-forward architecture
-#[derive(Debug, Clone)]
-struct AppState {
-    zoom_level: f64,
-    pattern_type: PatternType,
-    view_x: f64,
-    view_y: f64,
-    source_width: usize,
-    source_height: usize,
-    file_name: Option<String>,
-    primary_text: Option<String>,
-    secondary_text: Option<String>,
-}
-
-impl Default for AppState {
-    fn default() -> Self {
-        Self {
-            zoom_level: 1.0,
-            pattern_type: PatternType::Text,
-            view_x: 0.0,
-            view_y: 0.0,
-            source_width: 800,
-            source_height: 600,
-            file_name: None,
-            primary_text: Some("COMING SOON".to_string()),
-            secondary_text: None,
-        }
-    }
-}
-
-define_class!(
-    #[unsafe(super = NSObject)]
-    #[thread_kind = MainThreadOnly]
-    #[name = "AppDelegate"]
-    #[ivars = AppDelegateIvars]
-    struct AppDelegate;
-
-    unsafe impl NSObjectProtocol for AppDelegate {}
-
-    unsafe impl NSApplicationDelegate for AppDelegate {
-        #[unsafe(method(applicationDidFinishLaunching:))]
-        fn applicationDidFinishLaunching(&self, _notification: &NSNotification) {
-            println!("DEBUG: Application did finish launching");
-
-            let mtm = self.mtm();
-
-            let window = self.create_window(mtm);
-            let _ = self.ivars().window.set(window.clone());
-
-            window.setTitle(ns_string!("JP2 Viewer"));
-            window.center();
-
-            self.setup_image_view(&window, mtm);
-            self.setup_zoom_controls(&window, mtm);
-            self.add_buttons(&window, mtm);
-            self.setup_mouse_handling(&window);
-
-            // Initialize default state
-            {
-                let mut state = self.ivars().state.borrow_mut();
-                state.source_width = 800;
-                state.source_height = 600;
-                state.zoom_level = 1.0;
-                state.pattern_type = PatternType::Text;
-                state.primary_text = Some("COMING SOON".to_string());
-            }
-
-            // Initialize the pattern cache
-            let _ = self.ensure_pattern_cache();
-
-            // Render initial UI
-            let _ = self.render_viewport();
-
-            // Activate app and make window visible
-            let app = NSApplication::sharedApplication(mtm);
-            unsafe { app.activate() };
-            window.makeKeyAndOrderFront(None);
-        }
-    }
-
-    unsafe impl NSWindowDelegate for AppDelegate {
-        #[unsafe(method(windowWillClose:))]
-        fn windowWillClose(&self, _notification: &NSNotification) {
-            let mtm = self.mtm();
-            let app = NSApplication::sharedApplication(mtm);
-            unsafe { app.terminate(None) };
-        }
-    }
-
-    // Add custom methods for our delegate
-    impl AppDelegate {
-        #[unsafe(method(openFile:))]
-        fn openFile(&self, _sender: Option<&NSObject>) -> Bool {
-            println!("DEBUG: Opening file dialog");
-
-            let mtm = self.mtm();
-            let panel = unsafe { objc2_app_kit::NSOpenPanel::openPanel(mtm) };
-
-            unsafe {
-                panel.setCanChooseFiles(true);
+    // 30: H
+    [
+        [1, 0, 0, 0, 1],
+        [1, 0, 0, 0, 1],
+        [1, 1, 1, 1, 1],
+        [1, 0, 0, 0, 1],
+        [1, 0, 0, 0, 1],
+    ],
+    // 31: K
+    [
+        [1, 0, 0, 0, 1],
+        [1, 0, 0, 1, 0],
+        [1, 1, 1, 0, 0],
+        [1, 0, 0, 1, 0],
+        [1, 0, 0, 0, 1],
+    ],
+    // 32: Q
+    [
+        [0, 1, 1, 1, 0],
+        [1, 0, 0, 0, 1],
+        [1, 0, 1, 0, 1],
+        [1, 0, 0, 1, 0],
+        [0, 1, 1, 0, 1],
+    ],
+    // 33: U
+    [
+        [1, 0, 0, 0, 1],
+        [1, 0, 0, 0, 1],
+        [1, 0, 0, 0, 1],
+        [1, 0, 0, 0, 1],
+        [0, 1, 1, 1, 0],
+    ],
+    // 34: V
+    [
+        [1, 0, 0, 0, 1],
+        [1, 0, 0, 0, 1],
+        [1, 0, 0, 0, 1],
+        [0, 1, 0, 1, 0],
+        [0, 0, 1, 0, 0],
+    ],
+    // 35: W
+    [
+        [1, 0, 0, 0, 1],
+        [1, 0, 0, 0, 1],
+        [1, 0, 1, 0, 1],
+        [1, 1, 0, 1, 1],
+        [1, 0, 0, 0, 1],
+    ],
+    // 36: X
+    [
+        [1, 0, 0, 0, 1],
+        [0, 1, 0, 1, 0],
+        [0, 0, 1, 0, 0],
+        [0, 1, 0, 1, 0],
+        [1, 0, 0, 0, 1],
+    ],
+    // 37: Y
+    [
+        [1, 0, 0, 0, 1],
+        [0, 1, 0, 1, 0],
+        [0, 0, 1, 0, 0],
+        [0, 0, 1, 0, 0],
+        [0, 0, 1, 0, 0],
+    ],
+    // 38: Z
+    [
+        [1, 1, 1, 1, 1],
+        [0, 0, 0, 1, 0],
+        [0, 0, 1, 0, 0],
+        [0, 1, 0, 0, 0],
+        [1, 1, 1, 1, 1],
+    ],
+    // 39: a
+    [
+        [0, 0, 0, 0, 0],
+        [0, 1, 1, 1, 0],
+        [0, 0, 0, 1, 1],
+        [0, 1, 0, 0, 1],
+        [0, 0, 1, 1, 1],
+    ],
+    // 40: b
+    [
+        [1, 0, 0, 0, 0],
+        [1, 0, 0, 0, 0],
+        [1, 1, 1, 1, 0],
+        [1, 0, 0, 0, 1],
+        [1, 1, 1, 1, 0],
+    ],
+    // 41: c
+    [
+        [0, 0, 0, 0, 0],
+        [0, 1, 1, 1, 1],
+        [1, 0, 0, 0, 0],
+        [1, 0, 0, 0, 0],
+        [0, 1, 1, 1, 1],
+    ],
+    // 42: d
+    [
+        [0, 0, 0, 0, 1],
+        [0, 0, 0, 0, 1],
+        [0, 1, 1, 1, 1],
+        [1, 0, 0, 0, 1],
+        [0, 1, 1, 1, 1],
+    ],
+    // 43: e
+    [
+        [0, 0, 0, 0, 0],
+        [0, 1, 1, 1, 0],
+        [1, 1, 1, 1, 1],
+        [1, 0, 0, 0, 0],
+        [0, 1, 1, 1, 1],
+    ],
+    // 44: f
+    [
+        [0, 0, 1, 1, 0],
+        [0, 1, 0, 0, 0],
+        [1, 1, 1, 1, 0],
+        [0, 1, 0, 0, 0],
+        [0, 1, 0, 0, 0],
+    ],
+    // 45: g
+    [
+        [0, 1, 1, 1, 1],
+        [1, 0, 0, 0, 1],
+        [0, 1, 1, 1, 1],
+        [0, 0, 0, 0, 1],
+        [0, 1, 1, 1, 0],
+    ],
+    // 46: h
+    [
+        [1, 0, 0, 0, 0],
+        [1, 0, 0, 0, 0],
+        [1, 1, 1, 1, 0],
+        [1, 0, 0, 0, 1],
+        [1, 0, 0, 0, 1],
+    ],
+    // 47: i
+    [
+        [0, 0, 1, 0, 0],
+        [0, 0, 0, 0, 0],
+        [0, 1, 1, 0, 0],
+        [0, 0, 1, 0, 0],
+        [0, 1, 1, 1, 0],
+    ],
+    // 48: j
+    [
+        [0, 0, 0, 1, 0],
+        [0, 0, 0, 0, 0],
+        [0, 0, 0, 1, 0],
+        [1, 0, 0, 1, 0],
+        [0, 1, 1, 0, 0],
+    ],
+    // 49: k
+    [
+        [1, 0, 0, 0, 0],
+        [1, 0, 0, 1, 0],
+        [1, 1, 1, 0, 0],
+        [1, 0, 0, 1, 0],
+        [1, 0, 0, 0, 1],
+    ],
+    // 50: l
+    [
+        [0, 1, 1, 0, 0],
+        [0, 0, 1, 0, 0],
+        [0, 0, 1, 0, 0],
+        [0, 0, 1, 0, 0],
+        [0, 1, 1, 1, 0],
+    ],
+    // 51: m
+    [
+        [0, 0, 0, 0, 0],
+        [1, 1, 0, 1, 0],
+        [1, 0, 1, 0, 1],
+        [1, 0, 1, 0, 1],
+        [1, 0, 1, 0, 1],
+    ],
+    // 52: n
+    [
+        [0, 0, 0, 0, 0],
+        [1, 1, 1, 1, 0],
+        [1, 0, 0, 0, 1],
+        [1, 0, 0, 0, 1],
+        [1, 0, 0, 0, 1],
+    ],
+    // 53: o
+    [
+        [0, 0, 0, 0, 0],
+        [0, 1, 1, 1, 0],
+        [1, 0, 0, 0, 1],
+        [1, 0, 0, 0, 1],
+        [0, 1, 1, 1, 0],
+    ],
+    // 54: p
+    [
+        [1, 1, 1, 1, 0],
+        [1, 0, 0, 0, 1],
+        [1, 1, 1, 1, 0],
+        [1, 0, 0, 0, 0],
+        [1, 0, 0, 0, 0],
+    ],
+    // 55: q
+    [
+        [0, 1, 1, 1, 1],
+        [1, 0, 0, 0, 1],
+        [0, 1, 1, 1, 1],
+        [0, 0, 0, 0, 1],
+        [0, 0, 0, 0, 1],
+    ],
+    // 56: r
+    [
+        [0, 0, 0, 0, 0],
+        [1, 0, 1, 1, 0],
+        [1, 1, 0, 0, 0],
+        [1, 0, 0, 0, 0],
+        [1, 0, 0, 0, 0],
+    ],
+    // 57: s
+    [
+        [0, 0, 0, 0, 0],
+        [0, 1, 1, 1, 1],
+        [0, 1, 1, 0, 0],
+        [0, 0, 0, 1, 1],
+        [1, 1, 1, 1, 0],
+    ],
+    // 58: t
+    [
+        [0, 1, 0, 0, 0],
+        [1, 1, 1, 1, 0],
+        [0, 1, 0, 0, 0],
+        [0, 1, 0, 0, 0],
+        [0, 0, 1, 1, 0],
+    ],
+    // 59: u
+    [
+        [0, 0, 0, 0, 0],
+        [1, 0, 0, 0, 1],
+        [1, 0, 0, 0, 1],
+        [1, 0, 0, 0, 1],
+        [0, 1, 1, 1, 1],
+    ],
+    // 60: v
+    [
+        [0, 0, 0, 0, 0],
+        [1, 0, 0, 0, 1],
+        [1, 0, 0, 0, 1],
+        [0, 1, 0, 1, 0],
+        [0, 0, 1, 0, 0],
+    ],
+    // 61: w
+    [
+        [0, 0, 0, 0, 0],
+        [1, 0, 0, 0, 1],
+        [1, 0, 1, 0, 1],
+        [1, 0, 1, 0, 1],
+        [0, 1, 0, 1, 0],
+    ],
+    // 62: x
+    [
+        [1, 0, 0, 0, 1],
+        [0, 1, 0, 1, 0],
+        [0, 0, 1, 0, 0],
+        [0, 1, 0, 1, 0],
+        [1, 0, 0, 0, 1],
+    ],
+    // 63: y
+    [
+        [1, 0, 0, 0, 1],
+        [1, 0, 0, 0, 1],
+        [0, 1, 1, 1, 1],
+        [0, 0, 0, 0, 1],
+        [0, 1, 1, 1, 0],
+    ],
+    // 64: z
+    [
+        [0, 0, 0, 0, 0],
+        [1, 1, 1, 1, 1],
+        [0, 0, 1, 1, 0],
+        [0, 1, 0, 0, 0],
+        [1, 1, 1, 1, 1],
+    ],
+    // 65: , (comma)
+    [
+        [0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0],
+        [0, 0, 1, 0, 0],
+        [0, 1, 0, 0, 0],
+    ],
+    // 66: : (colon)
+    [
+        [0, 0, 0, 0, 0],
+        [0, 0, 1, 0, 0],
+        [0, 0, 0, 0, 0],
+        [0, 0, 1, 0, 0],
+        [0, 0, 0, 0, 0],
+    ],
+    // 67: / (slash)
+    [
+        [0, 0, 0, 0, 1],
+        [0, 0, 0, 1, 0],
+        [0, 0, 1, 0, 0],
+        [0, 1, 0, 0, 0],
+        [1, 0, 0, 0, 0],
+    ],
+    // 68: ( (left parenthesis)
+    [
+        [0, 0, 0, 1, 0],
+        [0, 0, 1, 0, 0],
+        [0, 0, 1, 0, 0],
+        [0, 0, 1, 0, 0],
+        [0, 0, 0, 1, 0],
+    ],
+    // 69: ) (right parenthesis)
+    [
+        [0, 1, 0, 0, 0],
+        [0, 0, 1, 0, 0],
+        [0, 0, 1, 0, 0],
+        [0, 0, 1, 0, 0],
+        [0, 1, 0, 0, 0],
+    ],
+    // 70: _ (underscore)
+    [
+        [0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0],
+        [1, 1, 1, 1, 1],
+    ],
+    // 71: missing glyph box, drawn for characters without a glyph
+    [
+        [1, 1, 1, 1, 1],
+        [1, 0, 0, 0, 1],
+        [1, 0, 0, 0, 1],
+        [1, 0, 0, 0, 1],
+        [1, 1, 1, 1, 1],
+    ],
+];
+
+/// Mapping from characters to their index in the BITMAP_CHARS array
+/// Unknown characters are drawn as the MISSING_GLYPH box
+const CHAR_INDICES: [(char, usize); 71] = [
+    ('C', 0),
+    ('O', 1),
+    ('M', 2),
+    ('I', 3),
+    ('N', 4),
+    ('G', 5),
+    ('S', 6),
+    ('P', 7),
+    ('J', 8),
+    ('2', 9),
+    (' ', 10),
+    ('F', 11),
+    ('L', 12),
+    ('E', 13),
+    ('D', 14),
+    ('T', 15),
+    ('A', 16),
+    ('R', 17),
+    ('B', 18),
+    ('0', 19),
+    ('1', 20),
+    ('3', 21),
+    ('4', 22),
+    ('5', 23),
+    ('6', 24),
+    ('7', 25),
+    ('8', 26),
+    ('9', 27),
+    ('-', 28),
+    ('.', 29),
+    ('H', 30),
+    ('K', 31),
+    ('Q', 32),
+    ('U', 33),
+    ('V', 34),
+    ('W', 35),
+    ('X', 36),
+    ('Y', 37),
+    ('Z', 38),
+    ('a', 39),
+    ('b', 40),
+    ('c', 41),
+    ('d', 42),
+    ('e', 43),
+    ('f', 44),
+    ('g', 45),
+    ('h', 46),
+    ('i', 47),
+    ('j', 48),
+    ('k', 49),
+    ('l', 50),
+    ('m', 51),
+    ('n', 52),
+    ('o', 53),
+    ('p', 54),
+    ('q', 55),
+    ('r', 56),
+    ('s', 57),
+    ('t', 58),
+    ('u', 59),
+    ('v', 60),
+    ('w', 61),
+    ('x', 62),
+    ('y', 63),
+    ('z', 64),
+    (',', 65),
+    (':', 66),
+    ('/', 67),
+    ('(', 68),
+    (')', 69),
+    ('_', 70),
+];
+
+/// Index of the box drawn for characters that have no glyph
+const MISSING_GLYPH: usize = 71;
+
+// Structure to hold source pattern and debug pixel data
+#[derive(Debug)]
+struct SourcePattern {
+    buffer: Vec<u8>,
+    width: usize,
+    height: usize,
+    bytes_per_row: usize,
+}
+
+impl SourcePattern {
+    // The n most prominent colors, most common first, found by median cut over
+    // a sample of the non-transparent pixels. May return fewer than n colors
+    // when the pattern has fewer distinct colors.
+    fn dominant_colors(&self, n: usize) -> Vec<[u8; 4]> {
+        const MAX_SAMPLES: usize = 16384;
+
+        let pixel_count = self.width * self.height;
+        let step = (pixel_count / MAX_SAMPLES).max(1);
+        let mut samples: Vec<[u8; 3]> = (0..pixel_count)
+            .step_by(step)
+            .filter_map(|i| {
+                let idx = px(
+                    &self.buffer,
+                    self.bytes_per_row,
+                    i % self.width,
+                    i / self.width,
+                );
+                let pixel = &self.buffer[idx..idx + 4];
+                (pixel[3] > 0).then(|| [pixel[0], pixel[1], pixel[2]])
+            })
+            .collect();
+        if samples.is_empty() || n == 0 {
+            return Vec::new();
+        }
+
+        // (channel, spread) of the channel with the widest spread in a bucket
+        let widest_channel = |bucket: &[[u8; 3]]| {
+            (0..3)
+                .map(|channel| {
+                    let values = bucket.iter().map(|sample| sample[channel]);
+                    let spread = values.clone().max().unwrap_or(0) - values.min().unwrap_or(0);
+                    (channel, spread)
+                })
+                .max_by_key(|&(_, spread)| spread)
+                .unwrap_or((0, 0))
+        };
+
+        // Buckets are ranges of `samples`; keep halving the most varied one
+        // at the median of its widest channel
+        let mut buckets: Vec<std::ops::Range<usize>> = Vec::new();
+        buckets.push(0..samples.len());
+        while buckets.len() < n {
+            let widest = buckets
+                .iter()
+                .enumerate()
+                .map(|(index, bucket)| (index, widest_channel(&samples[bucket.clone()])))
+                .max_by_key(|&(_, (_, spread))| spread);
+            let Some((index, (channel, spread))) = widest else {
+                break;
+            };
+            if spread == 0 {
+                break;
+            }
+
+            // Split where the channel value changes nearest the median, so
+            // a run of one value is never divided between two buckets
+            let bucket = buckets[index].clone();
+            let sorted = &mut samples[bucket.clone()];
+            sorted.sort_unstable_by_key(|sample| sample[channel]);
+            let pivot = sorted[sorted.len() / 2][channel];
+            let mut split = sorted.partition_point(|sample| sample[channel] < pivot);
+            if split == 0 {
+                split = sorted.partition_point(|sample| sample[channel] <= pivot);
+            }
+
+            buckets[index] = bucket.start..bucket.start + split;
+            buckets.push(bucket.start + split..bucket.end);
+        }
+
+        // Average each bucket, merging buckets that average to the same color
+        let mut colors: Vec<([u8; 4], usize)> = Vec::new();
+        for bucket in buckets {
+            let count = bucket.len();
+            let mut sum = [0usize; 3];
+            for sample in &samples[bucket] {
+                for channel in 0..3 {
+                    sum[channel] += sample[channel] as usize;
+                }
+            }
+            let color = [
+                (sum[0] / count) as u8,
+                (sum[1] / count) as u8,
+                (sum[2] / count) as u8,
+                255,
+            ];
+
+            match colors.iter_mut().find(|(existing, _)| *existing == color) {
+                Some((_, total)) => *total += count,
+                None => colors.push((color, count)),
+            }
+        }
+
+        colors.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+        colors.into_iter().map(|(color, _)| color).collect()
+    }
+
+    // Focus metric: variance of the Laplacian of the luminance, measured on a
+    // copy downsampled to at most SHARPNESS_MAX_SIDE pixels on its long side.
+    // Higher is sharper; a flat image scores 0.
+    fn sharpness(&self) -> f64 {
+        let (laplacian, _, _, _) = self.laplacian();
+        variance(&laplacian)
+    }
+
+    // The source rectangle (x, y, width, height) of the sharpest cell when
+    // the image is divided into a grid of `cells` x `cells`
+    fn sharpest_region(&self, cells: usize) -> Option<(usize, usize, usize, usize)> {
+        let (laplacian, width, height, scale) = self.laplacian();
+        let cells = cells.clamp(1, width.min(height).max(1));
+        if laplacian.is_empty() {
+            return None;
+        }
+
+        let mut best: Option<((usize, usize, usize, usize), f64)> = None;
+        for cell_y in 0..cells {
+            for cell_x in 0..cells {
+                let (left, right) = (cell_x * width / cells, (cell_x + 1) * width / cells);
+                let (top, bottom) = (cell_y * height / cells, (cell_y + 1) * height / cells);
+                let values: Vec<f64> = (top..bottom)
+                    .flat_map(|y| {
+                        laplacian[y * width + left..y * width + right]
+                            .iter()
+                            .copied()
+                    })
+                    .collect();
+                let score = variance(&values);
+                if best.is_none_or(|(_, best_score)| score > best_score) {
+                    let rect = (
+                        ((left + 1) as f64 * scale) as usize,
+                        ((top + 1) as f64 * scale) as usize,
+                        ((right - left) as f64 * scale).ceil() as usize,
+                        ((bottom - top) as f64 * scale).ceil() as usize,
+                    );
+                    best = Some((rect, score));
+                }
+            }
+        }
+
+        best.map(|(rect, _)| rect)
+    }
+
+    // 4-neighbour Laplacian of the downsampled luminance, along with the
+    // downsampled width and height and the source pixels per sample
+    fn laplacian(&self) -> (Vec<f64>, usize, usize, f64) {
+        let scale = (self.width.max(self.height) as f64 / SHARPNESS_MAX_SIDE as f64).max(1.0);
+        let width = ((self.width as f64 / scale) as usize).max(1);
+        let height = ((self.height as f64 / scale) as usize).max(1);
+
+        let luma: Vec<f64> = (0..width * height)
+            .map(|i| {
+                let src_x = (((i % width) as f64 * scale) as usize).min(self.width - 1);
+                let src_y = (((i / width) as f64 * scale) as usize).min(self.height - 1);
+                let idx = px(&self.buffer, self.bytes_per_row, src_x, src_y);
+                let pixel = &self.buffer[idx..idx + 3];
+                0.299 * pixel[0] as f64 + 0.587 * pixel[1] as f64 + 0.114 * pixel[2] as f64
+            })
+            .collect();
+
+        // Edge samples have no full neighbourhood, so the result covers the
+        // interior only and is empty for images under 3 samples across
+        if width < 3 || height < 3 {
+            return (Vec::new(), 0, 0, scale);
+        }
+        let inner_width = width - 2;
+        let inner_height = height - 2;
+        let laplacian = (0..inner_width * inner_height)
+            .map(|i| {
+                let x = i % inner_width + 1;
+                let y = i / inner_width + 1;
+                luma[(y - 1) * width + x]
+                    + luma[(y + 1) * width + x]
+                    + luma[y * width + x - 1]
+                    + luma[y * width + x + 1]
+                    - 4.0 * luma[y * width + x]
+            })
+            .collect();
+
+        (laplacian, inner_width, inner_height, scale)
+    }
+}
+
+// Long side of the copy that sharpness is measured on
+const SHARPNESS_MAX_SIDE: usize = 512;
+
+fn variance(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    values
+        .iter()
+        .map(|value| (value - mean).powi(2))
+        .sum::<f64>()
+        / values.len() as f64
+}
+
+// Enum to represent different pattern types
+#[derive(Debug, Clone, PartialEq)]
+enum PatternType {
+    Checkerboard,
+    Gradient,
+    Text,
+    // A generator added with AppDelegate::register_pattern_generator, by index
+    Custom(usize),
+    // Externally decoded pixels, tightly packed premultiplied RGBA. Shared so
+    // cloning the state stays cheap and the pattern cache compares pointers
+    // before contents.
+    Image {
+        rgba: Rc<[u8]>,
+        width: usize,
+        height: usize,
+    },
+}
+
+impl PatternType {
+    // The built-in patterns
+    const ALL: [PatternType; 3] = [
+        PatternType::Checkerboard,
+        PatternType::Gradient,
+        PatternType::Text,
+    ];
+}
+
+// Something that can fill a source pattern buffer. The built-in patterns
+// implement this, and further generators can be plugged in at runtime with
+// AppDelegate::register_pattern_generator.
+trait PatternGenerator: fmt::Debug {
+    // Fill a tightly packed RGBA buffer of width x height pixels
+    fn generate(&self, buffer: &mut [u8], width: usize, height: usize, bytes_per_row: usize);
+}
+
+// Image container formats recognized by the loader
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ImageFormat {
+    Jp2,
+    Png,
+    Jpeg,
+}
+
+impl ImageFormat {
+    const ALL: [ImageFormat; 3] = [ImageFormat::Jp2, ImageFormat::Png, ImageFormat::Jpeg];
+
+    fn name(self) -> &'static str {
+        match self {
+            ImageFormat::Jp2 => "JP2",
+            ImageFormat::Png => "PNG",
+            ImageFormat::Jpeg => "JPEG",
+        }
+    }
+
+    fn extensions(self) -> &'static [&'static str] {
+        match self {
+            ImageFormat::Jp2 => &["jp2"],
+            ImageFormat::Png => &["png"],
+            ImageFormat::Jpeg => &["jpg", "jpeg"],
+        }
+    }
+
+    // Whether files in this format can be displayed, not just recognized
+    fn decodes(self) -> bool {
+        matches!(self, ImageFormat::Jp2)
+    }
+}
+
+// Extensions of the files that actually load, shared by the Open panel and drag-and-drop
+fn openable_extensions() -> impl Iterator<Item = &'static str> {
+    ImageFormat::ALL
+        .into_iter()
+        .filter(|format| format.decodes())
+        .flat_map(|format| format.extensions().iter().copied())
+}
+
+// Errors produced while loading an image file
+#[derive(Debug)]
+enum DecodeError {
+    Io(std::io::Error),
+    // Not a format the loader handles; carries a best guess at what it is,
+    // such as "a GIF image" or "a .txt file"
+    UnsupportedFormat(String),
+    // The file claims a supported format but its contents couldn't be decoded
+    Corrupt(String),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Io(err) => write!(f, "failed to read file: {}", err),
+            DecodeError::UnsupportedFormat(guess) => write!(f, "unsupported format ({})", guess),
+            DecodeError::Corrupt(reason) => write!(f, "couldn't decode image: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<std::io::Error> for DecodeError {
+    fn from(err: std::io::Error) -> Self {
+        DecodeError::Io(err)
+    }
+}
+
+// Image parameters read from the file header without decoding any pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ImageInfo {
+    width: u32,
+    height: u32,
+    components: u16,
+    bits_per_component: u8,
+}
+
+// Raw file contents along with the format they were identified as
+#[derive(Debug)]
+struct LoadedImage {
+    format: ImageFormat,
+    bytes: Vec<u8>,
+    info: Option<ImageInfo>,
+}
+
+impl LoadedImage {
+    // One-line description such as "JP2, 1920×1080, RGB, 8-bit"
+    fn describe(&self) -> String {
+        let format = self.format.name();
+
+        match self.info {
+            Some(info) => {
+                let color = match info.components {
+                    1 => "Gray".to_string(),
+                    2 => "Gray+Alpha".to_string(),
+                    3 => "RGB".to_string(),
+                    4 => "RGBA".to_string(),
+                    n => format!("{} channels", n),
+                };
+                format!(
+                    "{}, {}×{}, {}, {}-bit",
+                    format, info.width, info.height, color, info.bits_per_component
+                )
+            }
+            None => format.to_string(),
+        }
+    }
+}
+
+/// Identify an image format from the magic numbers at the start of the file
+fn detect_format(bytes: &[u8]) -> Option<ImageFormat> {
+    // JP2 files open with a 12-byte signature box whose type is 'jP  ' (0x6A502020)
+    if bytes.len() >= 12 && bytes[4..8] == [0x6A, 0x50, 0x20, 0x20] {
+        Some(ImageFormat::Jp2)
+    } else if bytes.starts_with(b"\x89PNG") {
+        Some(ImageFormat::Png)
+    } else if bytes.starts_with(&[0xFF, 0xD8]) {
+        Some(ImageFormat::Jpeg)
+    } else {
+        None
+    }
+}
+
+/// Guess an image format from the file extension, used when sniffing fails
+fn format_from_extension(path: &Path) -> Option<ImageFormat> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    ImageFormat::ALL
+        .into_iter()
+        .find(|format| format.extensions().contains(&extension.as_str()))
+}
+
+/// Read an image file and determine its format, preferring the file contents
+/// over the extension so mislabeled files are still handled correctly
+fn load_image(path: &Path) -> Result<LoadedImage, DecodeError> {
+    let bytes = std::fs::read(path)?;
+
+    let format = detect_format(&bytes)
+        .or_else(|| format_from_extension(path))
+        .ok_or_else(|| DecodeError::UnsupportedFormat(guess_unsupported_format(&bytes, path)))?;
+
+    let info = read_image_info(format, &bytes);
+
+    Ok(LoadedImage {
+        format,
+        bytes,
+        info,
+    })
+}
+
+impl SourcePattern {
+    /// Decode a JP2 file into RGBA pixels. Gray, gray+alpha, RGB and RGBA
+    /// images are supported at any bit depth, as are sYCC (with or without
+    /// alpha) and CMYK, which are converted to RGB. Samples are scaled to 8
+    /// bits and alpha is premultiplied, matching the bitmaps the viewer
+    /// renders into.
+    fn from_jp2_bytes(bytes: &[u8]) -> Result<SourcePattern, DecodeError> {
+        use openjp2::openjpeg::*;
+
+        let corrupt = |reason: &str| DecodeError::Corrupt(reason.to_string());
+
+        // openjpeg pulls data through callbacks; the reader is freed along
+        // with the stream, which is destroyed before `bytes` goes out of scope
+        let reader = Box::into_raw(Box::new(Jp2Reader { bytes, offset: 0 }));
+        let stream = unsafe {
+            let stream = opj_stream_default_create(1);
+            opj_stream_set_read_function(stream, Some(jp2_reader_read));
+            opj_stream_set_skip_function(stream, Some(jp2_reader_skip));
+            opj_stream_set_seek_function(stream, Some(jp2_reader_seek));
+            opj_stream_set_user_data_length(stream, bytes.len() as u64);
+            opj_stream_set_user_data(stream, reader as *mut c_void, Some(jp2_reader_free));
+            stream
+        };
+        let codec = opj_create_decompress(OPJ_CODEC_JP2);
+        let mut image: *mut opj_image_t = std::ptr::null_mut();
+
+        let decoded = unsafe {
+            let mut parameters = opj_dparameters_t::default();
+            if codec.is_null() || opj_setup_decoder(codec, &mut parameters) == 0 {
+                Err(corrupt("decoder setup failed"))
+            } else if opj_read_header(stream, codec, &mut image) == 0 || image.is_null() {
+                Err(corrupt("invalid JP2 header"))
+            } else if opj_decode(codec, stream, image) == 0
+                || opj_end_decompress(codec, stream) == 0
+            {
+                Err(corrupt("codestream is damaged or truncated"))
+            } else {
+                jp2_image_to_pattern(&*image)
+            }
+        };
+
+        unsafe {
+            if !image.is_null() {
+                opj_image_destroy(image);
+            }
+            if !codec.is_null() {
+                opj_destroy_codec(codec);
+            }
+            opj_stream_destroy(stream);
+        }
+
+        decoded
+    }
+}
+
+// Convert a decoded openjpeg image to premultiplied 8-bit RGBA
+fn jp2_image_to_pattern(
+    image: &openjp2::openjpeg::opj_image_t,
+) -> Result<SourcePattern, DecodeError> {
+    use openjp2::openjpeg::COLOR_SPACE::*;
+
+    let components = if image.comps.is_null() {
+        &[][..]
+    } else {
+        unsafe { std::slice::from_raw_parts(image.comps, image.numcomps as usize) }
+    };
+    if !(1..=4).contains(&components.len()) {
+        return Err(DecodeError::Corrupt(format!(
+            "{} color components",
+            components.len()
+        )));
+    }
+
+    // Unspecified and unknown color spaces are read by component count
+    let color_space = image.color_space;
+    match color_space {
+        OPJ_CLRSPC_EYCC => {
+            return Err(DecodeError::UnsupportedFormat(
+                "a JPEG 2000 image in e-sYCC".to_string(),
+            ))
+        }
+        OPJ_CLRSPC_SYCC if components.len() < 3 => {
+            return Err(DecodeError::Corrupt(format!(
+                "{} components for sYCC",
+                components.len()
+            )))
+        }
+        OPJ_CLRSPC_CMYK if components.len() != 4 => {
+            return Err(DecodeError::Corrupt(format!(
+                "{} components for CMYK",
+                components.len()
+            )))
+        }
+        _ => {}
+    }
+
+    let width = image.x1.saturating_sub(image.x0) as usize;
+    let height = image.y1.saturating_sub(image.y0) as usize;
+    if width == 0 || height == 0 {
+        return Err(DecodeError::Corrupt("image is empty".to_string()));
+    }
+
+    // Each component may be subsampled, so look its samples up by position
+    let mut planes = Vec::new();
+    for component in components {
+        let (comp_width, comp_height) = (component.w as usize, component.h as usize);
+        if component.data.is_null() || comp_width == 0 || comp_height == 0 {
+            return Err(DecodeError::Corrupt("missing component data".to_string()));
+        }
+        let data = unsafe { std::slice::from_raw_parts(component.data, comp_width * comp_height) };
+        planes.push((component, data));
+    }
+
+    let sample = |index: usize, x: usize, y: usize| -> u8 {
+        let (component, data) = planes[index];
+        let comp_x = (x / component.dx.max(1) as usize).min(component.w as usize - 1);
+        let comp_y = (y / component.dy.max(1) as usize).min(component.h as usize - 1);
+        let precision = component.prec.clamp(1, 31);
+
+        // Shift signed samples into the unsigned range, then scale to 8 bits
+        let mut value = data[comp_y * component.w as usize + comp_x] as i64;
+        if component.sgnd != 0 {
+            value += 1 << (precision - 1);
+        }
+        let max = (1i64 << precision) - 1;
+        (value.clamp(0, max) * 255 / max) as u8
+    };
+
+    let bytes_per_row = width * 4;
+    let mut buffer = vec![0; bytes_per_row * height];
+    for y in 0..height {
+        for x in 0..width {
+            let (rgb, alpha) = match (color_space, planes.len()) {
+                (OPJ_CLRSPC_SYCC, count) => (
+                    sycc_to_rgb([sample(0, x, y), sample(1, x, y), sample(2, x, y)]),
+                    if count == 4 { sample(3, x, y) } else { 255 },
+                ),
+                (OPJ_CLRSPC_CMYK, _) => (
+                    cmyk_to_rgb([
+                        sample(0, x, y),
+                        sample(1, x, y),
+                        sample(2, x, y),
+                        sample(3, x, y),
+                    ]),
+                    255,
+                ),
+                (_, 1) => ([sample(0, x, y); 3], 255),
+                (_, 2) => ([sample(0, x, y); 3], sample(1, x, y)),
+                (_, 3) => ([sample(0, x, y), sample(1, x, y), sample(2, x, y)], 255),
+                _ => (
+                    [sample(0, x, y), sample(1, x, y), sample(2, x, y)],
+                    sample(3, x, y),
+                ),
+            };
+
+            let idx = px(&buffer, bytes_per_row, x, y);
+            for channel in 0..3 {
+                buffer[idx + channel] = ((rgb[channel] as u32 * alpha as u32 + 127) / 255) as u8;
+            }
+            buffer[idx + 3] = alpha;
+        }
+    }
+
+    Ok(SourcePattern {
+        buffer,
+        width,
+        height,
+        bytes_per_row,
+    })
+}
+
+// Convert an 8-bit sYCC sample (chroma centered on 128) to RGB with the
+// BT.601 coefficients sYCC is defined with
+fn sycc_to_rgb([luma, blue, red]: [u8; 3]) -> [u8; 3] {
+    let luma = luma as f64;
+    let (blue, red) = (blue as f64 - 128.0, red as f64 - 128.0);
+    [
+        luma + 1.402 * red,
+        luma - 0.344136 * blue - 0.714136 * red,
+        luma + 1.772 * blue,
+    ]
+    .map(|channel| channel.round().clamp(0.0, 255.0) as u8)
+}
+
+// Convert an 8-bit CMYK sample to RGB, without a color profile
+fn cmyk_to_rgb([cyan, magenta, yellow, black]: [u8; 4]) -> [u8; 3] {
+    let white = 255 - black as u32;
+    [cyan, magenta, yellow].map(|ink| ((255 - ink as u32) * white / 255) as u8)
+}
+
+// In-memory source for openjpeg's stream callbacks
+struct Jp2Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+extern "C" fn jp2_reader_read(buffer: *mut c_void, len: usize, data: *mut c_void) -> usize {
+    let reader = unsafe { &mut *(data as *mut Jp2Reader) };
+    let remaining = reader.bytes.len() - reader.offset;
+    if buffer.is_null() || remaining == 0 {
+        // openjpeg's end-of-stream marker is (size_t)-1
+        return usize::MAX;
+    }
+
+    let count = remaining.min(len);
+    let out = unsafe { std::slice::from_raw_parts_mut(buffer as *mut u8, count) };
+    out.copy_from_slice(&reader.bytes[reader.offset..reader.offset + count]);
+    reader.offset += count;
+    count
+}
+
+extern "C" fn jp2_reader_skip(count: i64, data: *mut c_void) -> i64 {
+    let reader = unsafe { &mut *(data as *mut Jp2Reader) };
+    let target = (reader.offset as i64 + count).clamp(0, reader.bytes.len() as i64);
+    let skipped = target - reader.offset as i64;
+    reader.offset = target as usize;
+    skipped
+}
+
+extern "C" fn jp2_reader_seek(offset: i64, data: *mut c_void) -> i32 {
+    let reader = unsafe { &mut *(data as *mut Jp2Reader) };
+    match usize::try_from(offset) {
+        Ok(offset) if offset <= reader.bytes.len() => {
+            reader.offset = offset;
+            1
+        }
+        _ => 0,
+    }
+}
+
+extern "C" fn jp2_reader_free(data: *mut c_void) {
+    drop(unsafe { Box::from_raw(data as *mut Jp2Reader) });
+}
+
+// Copies the pixels of a PatternType::Image, sampling the nearest pixel if
+// the source size differs from the image's
+#[derive(Debug)]
+struct ImagePattern<'a> {
+    rgba: &'a [u8],
+    width: usize,
+    height: usize,
+}
+
+impl PatternGenerator for ImagePattern<'_> {
+    fn generate(&self, buffer: &mut [u8], width: usize, height: usize, bytes_per_row: usize) {
+        let image_bytes_per_row = self.width * 4;
+        if image_bytes_per_row * self.height > self.rgba.len() {
+            return;
+        }
+
+        for y in 0..height {
+            let src_y = y * self.height / height;
+            if width == self.width {
+                // Same width: whole rows can be copied
+                let src = src_y * image_bytes_per_row;
+                let dst = y * bytes_per_row;
+                buffer[dst..dst + width * 4].copy_from_slice(&self.rgba[src..src + width * 4]);
+                continue;
+            }
+
+            for x in 0..width {
+                let src_x = x * self.width / width;
+                let src_idx = px(self.rgba, image_bytes_per_row, src_x, src_y);
+                let idx = px(buffer, bytes_per_row, x, y);
+                buffer[idx..idx + 4].copy_from_slice(&self.rgba[src_idx..src_idx + 4]);
+            }
+        }
+    }
+}
+
+/// Best guess at what an unsupported file is, for error messages: a known
+/// image signature if there is one, otherwise the file extension
+fn guess_unsupported_format(bytes: &[u8], path: &Path) -> String {
+    const SIGNATURES: [(&[u8], &str); 6] = [
+        (b"GIF8", "a GIF image"),
+        (b"II*\0", "a TIFF image"),
+        (b"MM\0*", "a TIFF image"),
+        (b"BM", "a BMP image"),
+        (b"%PDF", "a PDF document"),
+        (b"\xFF\x4F\xFF\x51", "a raw JPEG 2000 codestream"),
+    ];
+
+    if let Some((_, name)) = SIGNATURES
+        .iter()
+        .find(|(signature, _)| bytes.starts_with(signature))
+    {
+        return name.to_string();
+    }
+    if bytes.len() >= 12 && &bytes[4..8] == b"ftyp" {
+        return "a HEIF or other ISO media file".to_string();
+    }
+    if bytes.len() >= 12 && bytes.starts_with(b"RIFF") && &bytes[8..12] == b"WEBP" {
+        return "a WebP image".to_string();
+    }
+
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => format!("a .{} file", extension),
+        None => "unknown".to_string(),
+    }
+}
+
+/// Read dimensions and sample layout from the image header
+fn read_image_info(format: ImageFormat, bytes: &[u8]) -> Option<ImageInfo> {
+    match format {
+        ImageFormat::Jp2 => read_jp2_info(bytes),
+        ImageFormat::Png => read_png_info(bytes),
+        ImageFormat::Jpeg => read_jpeg_info(bytes),
+    }
+}
+
+fn read_u16_be(bytes: &[u8], offset: usize) -> Option<u16> {
+    let b = bytes.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([b[0], b[1]]))
+}
+
+fn read_u32_be(bytes: &[u8], offset: usize) -> Option<u32> {
+    let b = bytes.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+// Find the contents of the first box of the given type in a sequence of JP2 boxes
+fn find_jp2_box<'a>(mut bytes: &'a [u8], box_type: &[u8; 4]) -> Option<&'a [u8]> {
+    while bytes.len() >= 8 {
+        let length = read_u32_be(bytes, 0)? as usize;
+        let (header_len, box_len) = match length {
+            // Box extends to the end of the file
+            0 => (8, bytes.len()),
+            // 64-bit extended length follows the type
+            1 => (
+                16,
+                usize::try_from(u64::from_be_bytes(bytes.get(8..16)?.try_into().ok()?)).ok()?,
+            ),
+            _ => (8, length),
+        };
+
+        if box_len < header_len || box_len > bytes.len() {
+            return None;
+        }
+
+        if &bytes[4..8] == box_type {
+            return Some(&bytes[header_len..box_len]);
+        }
+
+        bytes = &bytes[box_len..];
+    }
+
+    None
+}
+
+// JP2: the image header box ('ihdr') lives inside the JP2 header superbox ('jp2h')
+fn read_jp2_info(bytes: &[u8]) -> Option<ImageInfo> {
+    let header = find_jp2_box(bytes, b"jp2h")?;
+    let ihdr = find_jp2_box(header, b"ihdr")?;
+
+    Some(ImageInfo {
+        height: read_u32_be(ihdr, 0)?,
+        width: read_u32_be(ihdr, 4)?,
+        components: read_u16_be(ihdr, 8)?,
+        // Stored as bit depth minus one, with the high bit flagging signed samples
+        bits_per_component: (*ihdr.get(10)? & 0x7F) + 1,
+    })
+}
+
+// PNG: the IHDR chunk always directly follows the 8-byte signature
+fn read_png_info(bytes: &[u8]) -> Option<ImageInfo> {
+    if bytes.get(12..16)? != b"IHDR" {
+        return None;
+    }
+
+    let components = match *bytes.get(25)? {
+        0 => 1, // Grayscale
+        2 => 3, // RGB
+        3 => 3, // Palette, expands to RGB
+        4 => 2, // Grayscale + alpha
+        6 => 4, // RGBA
+        _ => return None,
+    };
+
+    Some(ImageInfo {
+        width: read_u32_be(bytes, 16)?,
+        height: read_u32_be(bytes, 20)?,
+        components,
+        bits_per_component: *bytes.get(24)?,
+    })
+}
+
+// JPEG: walk the marker segments until the start-of-frame header
+fn read_jpeg_info(bytes: &[u8]) -> Option<ImageInfo> {
+    let mut offset = 2;
+
+    while offset + 4 <= bytes.len() {
+        if bytes[offset] != 0xFF {
+            return None;
+        }
+
+        let marker = bytes[offset + 1];
+        let segment_len = read_u16_be(bytes, offset + 2)? as usize;
+
+        // SOF0-SOF15, excluding DHT (C4), JPG (C8) and DAC (CC)
+        if (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            return Some(ImageInfo {
+                bits_per_component: *bytes.get(offset + 4)?,
+                height: read_u16_be(bytes, offset + 5)? as u32,
+                width: read_u16_be(bytes, offset + 7)? as u32,
+                components: *bytes.get(offset + 9)? as u16,
+            });
+        }
+
+        offset += 2 + segment_len;
+    }
+
+    None
+}
+
+// Height of the control bar along the bottom of the main window
+const CONTROLS_HEIGHT: f64 = 60.0;
+
+// Size of the debug border stroke and corner marker squares, in source pixels
+const DEBUG_BORDER_THICKNESS: usize = 3;
+const DEBUG_CORNER_SIZE: usize = 15;
+const MAX_DEBUG_BORDER_INSET: usize = 16;
+// Opacity of blended corner markers, out of 255
+const DEBUG_CORNER_BLEND_ALPHA: u8 = 128;
+
+// Smallest content size that keeps every control in the bottom bar visible
+const MIN_CONTENT_SIZE: NSSize = NSSize::new(320.0, CONTROLS_HEIGHT + 100.0);
+
+// Toolbar item identifiers
+const TOOLBAR_OPEN: &str = "Open";
+const TOOLBAR_SAVE_IMAGE: &str = "SaveImage";
+const TOOLBAR_GRADIENT: &str = "Gradient";
+const TOOLBAR_CHECKERBOARD: &str = "Checkerboard";
+const TOOLBAR_FIT: &str = "Fit";
+const TOOLBAR_ACTUAL_SIZE: &str = "ActualSize";
+const TOOLBAR_ZOOM_PRESETS: &str = "ZoomPresets";
+
+// Toolbar items as (identifier, label, SF Symbol name). The zoom presets item
+// is a popup button rather than an icon, so it has no symbol.
+const TOOLBAR_ITEMS: [(&str, &str, &str); 7] = [
+    (TOOLBAR_OPEN, "Open", "folder"),
+    (TOOLBAR_SAVE_IMAGE, "Save Image", "square.and.arrow.down"),
+    (TOOLBAR_GRADIENT, "Gradient", "circle.lefthalf.filled"),
+    (
+        TOOLBAR_CHECKERBOARD,
+        "Checkerboard",
+        "checkerboard.rectangle",
+    ),
+    (TOOLBAR_FIT, "Fit", "arrow.up.left.and.arrow.down.right"),
+    (TOOLBAR_ACTUAL_SIZE, "Actual Size", "1.magnifyingglass"),
+    (TOOLBAR_ZOOM_PRESETS, "Zoom", ""),
+];
+
+// Fixed zoom steps offered by the toolbar popup and the ⌘=/⌘- shortcuts
+const ZOOM_PRESETS: [f64; 5] = [0.25, 0.5, 1.0, 2.0, 4.0];
+
+// The smallest preset above `zoom`, or the largest preset if there is none
+fn next_zoom_preset(zoom: f64) -> f64 {
+    ZOOM_PRESETS
+        .into_iter()
+        .find(|preset| *preset > zoom + 1e-6)
+        .unwrap_or(ZOOM_PRESETS[ZOOM_PRESETS.len() - 1])
+}
+
+// The largest preset below `zoom`, or the smallest preset if there is none
+fn previous_zoom_preset(zoom: f64) -> f64 {
+    ZOOM_PRESETS
+        .into_iter()
+        .rev()
+        .find(|preset| *preset < zoom - 1e-6)
+        .unwrap_or(ZOOM_PRESETS[0])
+}
+
+// NSUserDefaults keys for persisted preferences
+const PREF_CHECKER_COLORS: [&str; 2] = ["CheckerColor0", "CheckerColor1"];
+const PREF_SHOW_DEBUG_BORDERS: &str = "ShowDebugBorders";
+const PREF_DEBUG_BORDER_INSET: &str = "DebugBorderInset";
+const PREF_BLEND_DEBUG_CORNERS: &str = "BlendDebugCorners";
+const PREF_DEBUG_BORDERS_ON_IMAGES: &str = "DebugBordersOnImages";
+const PREF_TOOL_MODIFIERS: [&str; 1] = ["ZoomAnchorModifier"];
+const PREF_SCROLL_STYLE_PANNING: &str = "ScrollStylePanning";
+const PREF_PAN_SENSITIVITY: &str = "PanSensitivity";
+const PREF_SNAP_PAN_TO_PIXELS: &str = "SnapPanToPixels";
+const PREF_ZOOM_GESTURE_SENSITIVITY: &str = "ZoomGestureSensitivity";
+const PREF_AUTO_HIDE_CONTROLS: &str = "AutoHideControlsInFullScreen";
+const PREF_TEXT_SCALE: &str = "TextScale";
+const PREF_TEXT_DIRECTION: &str = "TextDirection";
+const PREF_SMOOTH_CHECKERBOARD: &str = "SmoothCheckerboard";
+const PREF_VIEW_BACKGROUND: &str = "ViewBackground";
+const PREF_REMEMBER_FILE_VIEW_STATE: &str = "RememberFileViewState";
+const PREF_FILE_VIEW_STATES: &str = "FileViewStates";
+
+// Most files whose view state is remembered; the least recently viewed
+// entries are dropped beyond this
+const MAX_FILE_VIEW_STATES: usize = 50;
+
+// Keys of the number dictionary stored for each file under
+// PREF_FILE_VIEW_STATES, which is itself keyed by path
+const VIEW_STATE_ZOOM: &str = "Zoom";
+const VIEW_STATE_VIEW_X: &str = "ViewX";
+const VIEW_STATE_VIEW_Y: &str = "ViewY";
+// Seconds since 1970 when the file was last viewed, for dropping the oldest
+const VIEW_STATE_LAST_VIEWED: &str = "LastViewed";
+
+// Zoom and pan remembered for a file
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ViewState {
+    zoom_level: f64,
+    view_x: f64,
+    view_y: f64,
+}
+
+impl ViewState {
+    // The numbers stored for this view state, by key
+    fn fields(&self) -> [(&'static str, f64); 3] {
+        [
+            (VIEW_STATE_ZOOM, self.zoom_level),
+            (VIEW_STATE_VIEW_X, self.view_x),
+            (VIEW_STATE_VIEW_Y, self.view_y),
+        ]
+    }
+
+    // Rebuild a view state from its stored numbers, if they're all present
+    fn from_fields(field: impl Fn(&str) -> Option<f64>) -> Option<ViewState> {
+        let number = |key| field(key).filter(|value: &f64| value.is_finite());
+        Some(ViewState {
+            zoom_level: number(VIEW_STATE_ZOOM)?.clamp(0.1, 10.0),
+            view_x: number(VIEW_STATE_VIEW_X)?,
+            view_y: number(VIEW_STATE_VIEW_Y)?,
+        })
+    }
+}
+
+// A number from one file's view state dictionary
+fn view_state_number(entry: &NSDictionary, key: &str) -> Option<f64> {
+    let value = entry.objectForKey(&NSString::from_str(key))?;
+    value
+        .downcast_ref::<NSNumber>()
+        .map(|number| number.as_f64())
+}
+
+// Remembered view states: a dictionary from file path to a dictionary of
+// numbers keyed by the VIEW_STATE_* names
+fn file_view_states(
+    defaults: &NSUserDefaults,
+) -> Option<Retained<NSDictionary<NSString, AnyObject>>> {
+    unsafe { defaults.dictionaryForKey(&NSString::from_str(PREF_FILE_VIEW_STATES)) }
+}
+
+// The view state remembered for a file
+fn saved_view_state(defaults: &NSUserDefaults, path: &str) -> Option<ViewState> {
+    let entry = file_view_states(defaults)?
+        .objectForKey(&NSString::from_str(path))?
+        .downcast::<NSDictionary>()
+        .ok()?;
+    ViewState::from_fields(|key| view_state_number(&entry, key))
+}
+
+// Store a file's view state as last viewed at `now` (seconds since 1970),
+// dropping the least recently viewed files beyond MAX_FILE_VIEW_STATES
+fn store_view_state(defaults: &NSUserDefaults, path: &NSString, view_state: ViewState, now: f64) {
+    // Keep the other files' entries, most recently viewed first
+    let mut entries: Vec<(Retained<NSString>, Retained<AnyObject>, f64)> =
+        file_view_states(defaults)
+            .map(|states| {
+                let (paths, entries) = states.to_vecs();
+                paths.into_iter().zip(entries).collect::<Vec<_>>()
+            })
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(entry_path, _)| **entry_path != *path)
+            .filter_map(|(entry_path, entry)| {
+                let last_viewed = entry
+                    .downcast_ref::<NSDictionary>()
+                    .and_then(|entry| view_state_number(entry, VIEW_STATE_LAST_VIEWED))?;
+                Some((entry_path, entry, last_viewed))
+            })
+            .collect();
+    entries.sort_by(|a, b| b.2.total_cmp(&a.2));
+    entries.truncate(MAX_FILE_VIEW_STATES - 1);
+
+    let mut fields = view_state.fields().to_vec();
+    fields.push((VIEW_STATE_LAST_VIEWED, now));
+    let keys: Vec<_> = fields
+        .iter()
+        .map(|(key, _)| NSString::from_str(key))
+        .collect();
+    let numbers: Vec<_> = fields
+        .iter()
+        .map(|(_, value)| NSNumber::new_f64(*value))
+        .collect();
+    let entry = NSDictionary::from_retained_objects(
+        &keys.iter().map(|key| &**key).collect::<Vec<_>>(),
+        &numbers,
+    );
+    entries.insert(
+        0,
+        (
+            path.copy(),
+            Retained::into_super(Retained::into_super(entry)),
+            now,
+        ),
+    );
+
+    let paths: Vec<_> = entries.iter().map(|(path, _, _)| &**path).collect();
+    let values: Vec<_> = entries.iter().map(|(_, entry, _)| entry.clone()).collect();
+    let states = NSDictionary::<NSString, AnyObject>::from_retained_objects(&paths, &values);
+    let value: &AnyObject = &states;
+    unsafe { defaults.setObject_forKey(Some(value), &NSString::from_str(PREF_FILE_VIEW_STATES)) };
+}
+
+// Points panned per line reported by a scroll wheel without precise deltas
+const SCROLL_LINE_POINTS: f64 = 10.0;
+
+// Seconds without mouse movement before the controls fade out in full screen
+const CONTROLS_IDLE_HIDE_DELAY: f64 = 3.0;
+
+// Seconds between checks for a finished background render benchmark
+const RENDER_STATS_POLL_INTERVAL: f64 = 0.2;
+
+// Per-pixel color filters applied to the viewport after sampling
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColorFilter {
+    // Make pixels within `tolerance` of `color` on every channel fully transparent
+    ChromaKey { color: [u8; 3], tolerance: u8 },
+    // Show colors as seen with a form of dichromatic color blindness
+    ColorBlind(ColorBlindType),
+}
+
+// Dichromacies simulated by ColorFilter::ColorBlind
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColorBlindType {
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+}
+
+impl ColorBlindType {
+    // In the order shown in the preferences pop-up, after "Off"
+    const ALL: [ColorBlindType; 3] = [
+        ColorBlindType::Protanopia,
+        ColorBlindType::Deuteranopia,
+        ColorBlindType::Tritanopia,
+    ];
+
+    fn title(self) -> &'static str {
+        match self {
+            ColorBlindType::Protanopia => "Protanopia (no red cones)",
+            ColorBlindType::Deuteranopia => "Deuteranopia (no green cones)",
+            ColorBlindType::Tritanopia => "Tritanopia (no blue cones)",
+        }
+    }
+
+    // Linear RGB simulation matrices at full severity from Machado, Oliveira
+    // and Fernandes (2009), derived from the LMS cone response model. Each
+    // row sums to 1, so grays are unchanged.
+    fn matrix(self) -> [[f64; 3]; 3] {
+        match self {
+            ColorBlindType::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            ColorBlindType::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            ColorBlindType::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        }
+    }
+}
+
+// Actions that can be bound to keyboard shortcuts
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ShortcutAction {
+    SaveScreenshot,
+    ToggleBordersOnly,
+    ToggleFreeze,
+    Print,
+    PrintActualSize,
+    ZoomInPreset,
+    ZoomOutPreset,
+    ToggleRulers,
+    TogglePinnedRegion,
+    ToggleSampling,
+    ShowDominantColors,
+    ToggleHelp,
+    NudgeUp,
+    NudgeDown,
+    NudgeLeft,
+    NudgeRight,
+}
+
+// A key binding: the key as reported by charactersIgnoringModifiers (which
+// keeps Shift, so "P" means Shift-P), whether Command and Option must be
+// held, and the description shown in the help overlay
+#[derive(Debug)]
+struct Shortcut {
+    key: &'static str,
+    command: bool,
+    option: bool,
+    action: ShortcutAction,
+    description: &'static str,
+}
+
+// Every keyboard shortcut. keyDown: dispatches from this table and the help
+// overlay is generated from it, so new shortcuts only need adding here.
+// Shortcuts that are menu key equivalents (⌘O, ⌘S, ⌘, and the Edit menu)
+// belong to the menu instead, so each key is bound in one place.
+const SHORTCUTS: [Shortcut; 16] = [
+    Shortcut {
+        key: "s",
+        command: false,
+        option: false,
+        action: ShortcutAction::SaveScreenshot,
+        description: "Save the current view to the Desktop",
+    },
+    Shortcut {
+        key: "b",
+        command: false,
+        option: false,
+        action: ShortcutAction::ToggleBordersOnly,
+        description: "Toggle showing only the debug borders",
+    },
+    Shortcut {
+        key: "f",
+        command: false,
+        option: false,
+        action: ShortcutAction::ToggleFreeze,
+        description: "Freeze or unfreeze the displayed image",
+    },
+    Shortcut {
+        key: "p",
+        command: true,
+        option: false,
+        action: ShortcutAction::Print,
+        description: "Print, scaled to fit the page",
+    },
+    Shortcut {
+        key: "P",
+        command: true,
+        option: false,
+        action: ShortcutAction::PrintActualSize,
+        description: "Print at actual size across multiple pages",
+    },
+    Shortcut {
+        key: "=",
+        command: true,
+        option: false,
+        action: ShortcutAction::ZoomInPreset,
+        description: "Zoom in to the next preset",
+    },
+    Shortcut {
+        key: "-",
+        command: true,
+        option: false,
+        action: ShortcutAction::ZoomOutPreset,
+        description: "Zoom out to the previous preset",
+    },
+    Shortcut {
+        key: "r",
+        command: false,
+        option: false,
+        action: ShortcutAction::ToggleRulers,
+        description: "Toggle rulers and the scale bar",
+    },
+    Shortcut {
+        key: "l",
+        command: false,
+        option: false,
+        action: ShortcutAction::TogglePinnedRegion,
+        description: "Lock the visible region in view across resizes",
+    },
+    Shortcut {
+        key: "i",
+        command: false,
+        option: false,
+        action: ShortcutAction::ToggleSampling,
+        description: "Cycle sampling modes and report how much the view changes",
+    },
+    Shortcut {
+        key: "d",
+        command: false,
+        option: false,
+        action: ShortcutAction::ShowDominantColors,
+        description: "Show the dominant colors of the source",
+    },
+    Shortcut {
+        key: "?",
+        command: false,
+        option: false,
+        action: ShortcutAction::ToggleHelp,
+        description: "Show or hide this help",
+    },
+    Shortcut {
+        key: UP_ARROW_KEY,
+        command: false,
+        option: true,
+        action: ShortcutAction::NudgeUp,
+        description: "Pan up by one source pixel",
+    },
+    Shortcut {
+        key: DOWN_ARROW_KEY,
+        command: false,
+        option: true,
+        action: ShortcutAction::NudgeDown,
+        description: "Pan down by one source pixel",
+    },
+    Shortcut {
+        key: LEFT_ARROW_KEY,
+        command: false,
+        option: true,
+        action: ShortcutAction::NudgeLeft,
+        description: "Pan left by one source pixel",
+    },
+    Shortcut {
+        key: RIGHT_ARROW_KEY,
+        command: false,
+        option: true,
+        action: ShortcutAction::NudgeRight,
+        description: "Pan right by one source pixel",
+    },
+];
+
+// Arrow keys as reported by charactersIgnoringModifiers
+const UP_ARROW_KEY: &str = "\u{F700}"; // NSUpArrowFunctionKey
+const DOWN_ARROW_KEY: &str = "\u{F701}"; // NSDownArrowFunctionKey
+const LEFT_ARROW_KEY: &str = "\u{F702}"; // NSLeftArrowFunctionKey
+const RIGHT_ARROW_KEY: &str = "\u{F703}"; // NSRightArrowFunctionKey
+
+fn shortcut_for(key: &str, command: bool, option: bool) -> Option<&'static Shortcut> {
+    SHORTCUTS.iter().find(|shortcut| {
+        shortcut.key == key && shortcut.command == command && shortcut.option == option
+    })
+}
+
+// How a shortcut's key is shown in the help overlay
+fn key_label(key: &str) -> String {
+    match key {
+        UP_ARROW_KEY => "↑".to_string(),
+        DOWN_ARROW_KEY => "↓".to_string(),
+        LEFT_ARROW_KEY => "←".to_string(),
+        RIGHT_ARROW_KEY => "→".to_string(),
+        _ => key.to_uppercase(),
+    }
+}
+
+// One line per shortcut, e.g. "⌘P    Print, scaled to fit the page"
+fn shortcut_help_text() -> String {
+    SHORTCUTS
+        .iter()
+        .map(|shortcut| {
+            let shift = shortcut.key.chars().any(|c| c.is_ascii_uppercase());
+            let key = format!(
+                "{}{}{}{}",
+                if shift { "⇧" } else { "" },
+                if shortcut.option { "⌥" } else { "" },
+                if shortcut.command { "⌘" } else { "" },
+                key_label(shortcut.key)
+            );
+            format!("{:<5} {}", key, shortcut.description)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Layout direction of the text pattern. The bitmap font has no shaping, so
+// right-to-left is simply the characters in reverse visual order.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum TextDirection {
+    #[default]
+    LeftToRight,
+    RightToLeft,
+    // Each line is a column read top to bottom, with columns ordered right to left
+    TopToBottom,
+}
+
+impl TextDirection {
+    // In the order shown in the preferences pop-up and stored in the defaults
+    const ALL: [TextDirection; 3] = [
+        TextDirection::LeftToRight,
+        TextDirection::RightToLeft,
+        TextDirection::TopToBottom,
+    ];
+
+    fn title(self) -> &'static str {
+        match self {
+            TextDirection::LeftToRight => "Left to right",
+            TextDirection::RightToLeft => "Right to left",
+            TextDirection::TopToBottom => "Vertical",
+        }
+    }
+}
+
+// Color shown around the image and through its transparent pixels
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum ViewBackground {
+    // The scroll view's standard background, which follows the system appearance
+    #[default]
+    System,
+    Black,
+    MidGray,
+    White,
+}
+
+impl ViewBackground {
+    // In the order shown in the preferences pop-up and stored in the defaults
+    const ALL: [ViewBackground; 4] = [
+        ViewBackground::System,
+        ViewBackground::Black,
+        ViewBackground::MidGray,
+        ViewBackground::White,
+    ];
+
+    fn title(self) -> &'static str {
+        match self {
+            ViewBackground::System => "System",
+            ViewBackground::Black => "Black",
+            ViewBackground::MidGray => "Mid gray",
+            ViewBackground::White => "White",
+        }
+    }
+
+    // Fixed colors as RGBA; System has none outside AppKit, so it's transparent
+    fn rgba(self) -> [u8; 4] {
+        match self {
+            ViewBackground::System => [0, 0, 0, 0],
+            ViewBackground::Black => [0, 0, 0, 255],
+            ViewBackground::MidGray => [128, 128, 128, 255],
+            ViewBackground::White => [255, 255, 255, 255],
+        }
+    }
+
+    fn color(self) -> Retained<NSColor> {
+        match self {
+            ViewBackground::System => unsafe { NSColor::controlBackgroundColor() },
+            other => rgba_to_ns_color(other.rgba()),
+        }
+    }
+}
+
+// Tools used by clicking with a modifier key held; the modifier for each is
+// configurable in Preferences
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ModifierTool {
+    // Pin the clicked source pixel as the zoom anchor
+    ZoomAnchor,
+}
+
+impl ModifierTool {
+    // In the order shown in Preferences; indexes AppState::tool_modifiers and
+    // PREF_TOOL_MODIFIERS
+    const ALL: [ModifierTool; 1] = [ModifierTool::ZoomAnchor];
+
+    fn title(self) -> &'static str {
+        match self {
+            ModifierTool::ZoomAnchor => "Set zoom anchor:",
+        }
+    }
+
+    fn default_modifier(self) -> ToolModifier {
+        match self {
+            ModifierTool::ZoomAnchor => ToolModifier::Shift,
+        }
+    }
+}
+
+// A modifier key that can be assigned to a ModifierTool
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ToolModifier {
+    Shift,
+    Option,
+    Command,
+}
+
+impl ToolModifier {
+    // In the order shown in the preferences pop-ups and stored in the defaults.
+    // Control is left out: Control-click opens the context menu.
+    const ALL: [ToolModifier; 3] = [
+        ToolModifier::Shift,
+        ToolModifier::Option,
+        ToolModifier::Command,
+    ];
+
+    fn title(self) -> &'static str {
+        match self {
+            ToolModifier::Shift => "⇧ Shift-click",
+            ToolModifier::Option => "⌥ Option-click",
+            ToolModifier::Command => "⌘ Command-click",
+        }
+    }
+
+    fn flag(self) -> NSEventModifierFlags {
+        match self {
+            ToolModifier::Shift => NSEventModifierFlags::Shift,
+            ToolModifier::Option => NSEventModifierFlags::Option,
+            ToolModifier::Command => NSEventModifierFlags::Command,
+        }
+    }
+}
+
+// How source pixels are sampled when rendering the viewport
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum SamplingMode {
+    // One source pixel per output pixel; crisp, but aliases at fractional zoom
+    #[default]
+    Nearest,
+    // Average a grid of samples across each output pixel's footprint
+    Supersampled,
+    // Interpolate between the four nearest source pixels when zoomed in, and
+    // box-filter like Supersampled when zoomed out so no pixels are dropped
+    Bilinear,
+}
+
+impl SamplingMode {
+    // The order the sampling toggle cycles through
+    const ALL: [SamplingMode; 3] = [
+        SamplingMode::Nearest,
+        SamplingMode::Bilinear,
+        SamplingMode::Supersampled,
+    ];
+
+    fn title(self) -> &'static str {
+        match self {
+            SamplingMode::Nearest => "nearest neighbor",
+            SamplingMode::Supersampled => "supersampled",
+            SamplingMode::Bilinear => "bilinear",
+        }
+    }
+
+    // Short label for the toggle button next to the zoom slider
+    fn button_title(self) -> &'static str {
+        match self {
+            SamplingMode::Nearest => "Nearest",
+            SamplingMode::Supersampled => "Supersampled",
+            SamplingMode::Bilinear => "Bilinear",
+        }
+    }
+
+    fn next(self) -> SamplingMode {
+        let index = SamplingMode::ALL
+            .iter()
+            .position(|mode| *mode == self)
+            .unwrap_or(0);
+        SamplingMode::ALL[(index + 1) % SamplingMode::ALL.len()]
+    }
+}
+
+// Render-time options that don't require regenerating the source pattern
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct RenderOptions {
+    // Draw the debug borders and corner markers over the rendered pixels
+    debug_borders: bool,
+    // Distance in source pixels from the source edges to the debug borders
+    debug_border_inset: usize,
+    // Draw the corner markers translucent so the corner pixels show through
+    blend_debug_corners: bool,
+    borders_only: bool,
+    // Draw source-pixel rulers along the top and left and a scale bar
+    rulers: bool,
+    sampling: SamplingMode,
+}
+
+// Thickness of the ruler bands, in viewport pixels
+const RULER_SIZE: usize = 16;
+// Labeled ruler ticks are at least this many viewport pixels apart
+const RULER_MIN_TICK_SPACING: f64 = 50.0;
+
+// Minimum samples per axis taken by SamplingMode::Supersampled. Further out
+// the grid grows to one sample per source pixel so none are skipped.
+const SUPERSAMPLE_GRID: usize = 4;
+
+// How mouse drags are translated into panning
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PanDirection {
+    // "Natural" panning: the image follows the cursor, as if grabbing a sheet of paper
+    Grab,
+    // "Classic" panning: the view follows the cursor, so the image moves the opposite way
+    // (like dragging scroll bars)
+    Scroll,
+}
+
+// Custom image view that forwards mouse events to our app delegate
+define_class!(
+    #[unsafe(super = NSImageView)]
+    #[thread_kind = MainThreadOnly]
+    #[name = "CustomImageView"]
+    #[derive(Debug)]
+    struct CustomImageView;
+
+    unsafe impl NSObjectProtocol for CustomImageView {}
+
+    impl CustomImageView {
+        #[unsafe(method(mouseDown:))]
+        fn mouseDown(&self, event: &NSEvent) {
+            if let Some(delegate) = self.get_app_delegate() {
+                unsafe {
+                    let _: Bool = msg_send![delegate, mouseDown: event];
+                }
+            }
+
+            unsafe {
+                let _: () = msg_send![super(self), mouseDown: event];
+            }
+        }
+
+        #[unsafe(method(mouseDragged:))]
+        fn mouseDragged(&self, event: &NSEvent) {
+            if let Some(delegate) = self.get_app_delegate() {
+                unsafe {
+                    let _: Bool = msg_send![delegate, mouseDragged: event];
+                }
+            }
+
+            unsafe {
+                let _: () = msg_send![super(self), mouseDragged: event];
+            }
+        }
+
+        #[unsafe(method(mouseUp:))]
+        fn mouseUp(&self, event: &NSEvent) {
+            if let Some(delegate) = self.get_app_delegate() {
+                unsafe {
+                    let _: Bool = msg_send![delegate, mouseUp: event];
+                }
+            }
+
+            unsafe {
+                let _: () = msg_send![super(self), mouseUp: event];
+            }
+        }
+
+        // Scrolls pan or, with Command held, zoom; any the delegate doesn't
+        // handle go on to super
+        #[unsafe(method(scrollWheel:))]
+        fn scrollWheel(&self, event: &NSEvent) {
+            if let Some(delegate) = self.get_app_delegate() {
+                let zoomed: Bool = unsafe { msg_send![delegate, scrollWheel: event] };
+                if zoomed.as_bool() {
+                    return;
+                }
+            }
+
+            unsafe {
+                let _: () = msg_send![super(self), scrollWheel: event];
+            }
+        }
+
+        #[unsafe(method(mouseMoved:))]
+        fn mouseMoved(&self, event: &NSEvent) {
+            if let Some(delegate) = self.get_app_delegate() {
+                unsafe {
+                    let _: Bool = msg_send![delegate, mouseMoved: event];
+                }
+            }
+
+            unsafe {
+                let _: () = msg_send![super(self), mouseMoved: event];
+            }
+        }
+
+        // Dropping a .jp2 file opens it. The view is outlined while a drop
+        // would be accepted.
+        #[unsafe(method(draggingEntered:))]
+        fn draggingEntered(&self, sender: &AnyObject) -> NSDragOperation {
+            if dragged_image_url(sender).is_none() {
+                return NSDragOperation::None;
+            }
+
+            unsafe { self.setImageFrameStyle(NSImageFrameStyle::GrayBezel) };
+            NSDragOperation::Copy
+        }
+
+        #[unsafe(method(draggingExited:))]
+        fn draggingExited(&self, _sender: Option<&AnyObject>) {
+            unsafe { self.setImageFrameStyle(NSImageFrameStyle::None) };
+        }
+
+        #[unsafe(method(performDragOperation:))]
+        fn performDragOperation(&self, sender: &AnyObject) -> Bool {
+            unsafe { self.setImageFrameStyle(NSImageFrameStyle::None) };
+
+            let (Some(url), Some(delegate)) = (dragged_image_url(sender), self.get_app_delegate())
+            else {
+                return Bool::NO;
+            };
+            unsafe { msg_send![delegate, openURL: &*url] }
+        }
+
+        #[unsafe(method(acceptsFirstResponder))]
+        fn acceptsFirstResponder(&self) -> bool {
+            true
+        }
+
+        #[unsafe(method(keyDown:))]
+        fn keyDown(&self, event: &NSEvent) {
+            if let Some(delegate) = self.get_app_delegate() {
+                let handled: Bool = unsafe { msg_send![delegate, keyDown: event] };
+                if handled.as_bool() {
+                    return;
+                }
+            }
+
+            // Let unhandled keys continue up the responder chain
+            unsafe {
+                let _: () = msg_send![super(self), keyDown: event];
+            }
+        }
+    }
+);
+
+impl CustomImageView {
+    fn new(mtm: MainThreadMarker, frame: NSRect) -> Retained<Self> {
+        let this = Self::alloc(mtm);
+        unsafe {
+            let obj: Retained<Self> = msg_send![this, initWithFrame: frame];
+            obj
+        }
+    }
+
+    fn get_app_delegate(&self) -> Option<&AnyObject> {
+        let mtm = self.mtm();
+        let app = NSApplication::sharedApplication(mtm);
+
+        unsafe {
+            let delegate: *const AnyObject = msg_send![&*app, delegate];
+            if delegate.is_null() {
+                None
+            } else {
+                Some(&*delegate)
+            }
+        }
+    }
+}
+
+// The file URL on a drag's pasteboard, if it names a file we can open
+fn dragged_image_url(dragging_info: &AnyObject) -> Option<Retained<NSURL>> {
+    let url: Option<Retained<NSURL>> = unsafe {
+        let pasteboard: Retained<NSPasteboard> = msg_send![dragging_info, draggingPasteboard];
+        msg_send![NSURL::class(), URLFromPasteboard: &*pasteboard]
+    };
+    url.filter(|url| {
+        unsafe { url.pathExtension() }.is_some_and(|extension| {
+            let extension = extension.to_string();
+            openable_extensions().any(|openable| extension.eq_ignore_ascii_case(openable))
+        })
+    })
+}
+
+// Add the CachedSourcePattern struct
+#[derive(Debug)]
+struct CachedSourcePattern {
+    pattern: SourcePattern,
+    pattern_type: PatternType,
+    primary_text: Option<String>,
+    secondary_text: Option<String>,
+    checker_colors: [[u8; 4]; 2],
+    text_scale: f64,
+    text_direction: TextDirection,
+    source_width: usize,
+    source_height: usize,
+}
+
+// Define the app delegate with ivars
+#[derive(Debug, Default)]
+struct AppDelegateIvars {
+    window: OnceCell<Retained<NSWindow>>,
+    scroll_view: OnceCell<Retained<NSScrollView>>,
+    image_view: OnceCell<Retained<CustomImageView>>,
+    selected_file_path: RefCell<Option<Retained<NSURL>>>,
+    decoded_image: RefCell<Option<Retained<NSImage>>>,
+    zoom_slider: OnceCell<Retained<NSSlider>>,
+    // Cycles the sampling mode; its title names the current mode
+    sampling_button: OnceCell<Retained<NSButton>>,
+    actual_size_button: OnceCell<Retained<NSButton>>,
+    preferences_window: OnceCell<Retained<NSWindow>>,
+    status_label: OnceCell<Retained<NSTextField>>,
+    help_overlay: OnceCell<Retained<NSTextField>>,
+    last_mouse_location: RefCell<NSPoint>,
+    is_panning: RefCell<bool>,
+    magnification_recognizer: OnceCell<Retained<NSMagnificationGestureRecognizer>>,
+    base_zoom_level: RefCell<f64>,
+    state: RefCell<AppState>,
+    cached_pattern: RefCell<Option<CachedSourcePattern>>,
+    // Generators for PatternType::Custom, indexed by the variant's value
+    custom_patterns: RefCell<Vec<Rc<dyn PatternGenerator>>>,
+    // Pending timer that hides the controls after the mouse goes idle in full screen
+    idle_timer: RefCell<Option<Retained<NSTimer>>>,
+    dominant_colors_window: OnceCell<Retained<NSWindow>>,
+    // Zoom preset popup in the toolbar, once the toolbar has created it
+    zoom_preset_popup: RefCell<Option<Retained<NSPopUpButton>>>,
+    // Status message from the render benchmark running in the background
+    render_stats_job: RefCell<Option<mpsc::Receiver<String>>>,
+}
+
+// State container for state-forward architecture
+#[derive(Debug, Clone)]
+struct AppState {
+    zoom_level: f64,
+    pattern_type: PatternType,
+    view_x: f64,
+    view_y: f64,
+    // Source point kept stationary on screen while zooming, set by the zoom anchor click tool
+    zoom_anchor: Option<(f64, f64)>,
+    // Source rectangle (x, y, width, height) kept framed across window resizes
+    // and pattern changes; while set, zoom and pan are derived from it
+    pinned_region: Option<(f64, f64, f64, f64)>,
+    // Size of the visible area in image pixels as of the last render; zooming
+    // keeps the pan within it
+    viewport_size: (f64, f64),
+    pan_direction: PanDirection,
+    // Multiplier applied to mouse drag distances when panning
+    pan_sensitivity: f64,
+    // Exponent applied to the pinch magnification: 2.0 zooms twice as far
+    // (in log scale) for the same gesture, 0.5 half as far
+    zoom_gesture_sensitivity: f64,
+    // At integer zoom, draw from a whole-source-pixel offset so every source
+    // pixel covers the same number of screen pixels
+    snap_pan_to_pixels: bool,
+    // Restore each file's last zoom and pan when it's reopened
+    remember_file_view_state: bool,
+    source_width: usize,
+    source_height: usize,
+    file_name: Option<String>,
+    primary_text: Option<String>,
+    secondary_text: Option<String>,
+    // RGBA colors of the checkerboard squares; index 0 is the top-left square
+    checker_colors: [[u8; 4]; 2],
+    // Supersample the checkerboard at fractional zoom to smooth its edges
+    smooth_checkerboard: bool,
+    // Sampling for every pattern; smooth_checkerboard can still upgrade
+    // the checkerboard to supersampling when this is Nearest
+    sampling: SamplingMode,
+    show_debug_borders: bool,
+    // Inset of the debug borders from the source edges, so the outermost
+    // source pixels stay visible
+    debug_border_inset: usize,
+    blend_debug_corners: bool,
+    // Also draw debug borders over opened images, where they hide the real
+    // edge pixels; off by default
+    debug_borders_on_images: bool,
+    // Size of the text pattern relative to its default, which is itself
+    // proportional to the canvas (32x40 pixel glyphs on an 800x600 canvas)
+    text_scale: f64,
+    text_direction: TextDirection,
+    view_background: ViewBackground,
+    // Modifier key that triggers each ModifierTool, indexed like ModifierTool::ALL
+    tool_modifiers: [ToolModifier; ModifierTool::ALL.len()],
+    // Fade out the bottom controls in full screen while the mouse is idle
+    auto_hide_controls: bool,
+    // Render only the debug borders and corner markers over a transparent
+    // background; applied at render time, so the cached pattern is untouched
+    borders_only: bool,
+    // Rulers and scale bar overlay; applied at render time
+    show_rulers: bool,
+    // Keep showing the current image and skip re-renders, for before/after
+    // comparisons; state changes still apply and show up when unfrozen
+    frozen: bool,
+    // Chroma key filter settings (kept while disabled so re-enabling restores them)
+    chroma_key_enabled: bool,
+    chroma_key_color: [u8; 3],
+    chroma_key_tolerance: u8,
+    // Color blindness simulation filter, if any
+    color_blind_simulation: Option<ColorBlindType>,
+}
+
+impl AppState {
+    // Set the zoom level (clamped to 0.1-10.0), adjusting the pan so the zoom
+    // anchor stays at the same place on screen when one is set. The pan is
+    // then clamped, so zooming out near an edge moves the anchor rather than
+    // leaving an offset that can't be drawn.
+    fn set_zoom(&mut self, zoom: f64) {
+        let zoom = zoom.clamp(0.1, 10.0);
+
+        // Choosing a zoom explicitly ends any pinned region
+        self.pinned_region = None;
+
+        // A source point s is drawn at s * zoom - view, so keep that constant
+        let (mut view_x, mut view_y) = (self.view_x, self.view_y);
+        if let Some((anchor_x, anchor_y)) = self.zoom_anchor {
+            view_x += anchor_x * (zoom - self.zoom_level);
+            view_y += anchor_y * (zoom - self.zoom_level);
+        }
+
+        self.zoom_level = zoom;
+        self.set_pan(view_x, view_y);
+    }
+
+    // Set the zoom level, adjusting the pan so the source point drawn at the
+    // given point (measured from the image's top-left corner) stays there,
+    // as far as the pan clamp allows. A zoom anchor set with the click tool
+    // takes precedence.
+    fn set_zoom_about_point(&mut self, zoom: f64, anchor_x: f64, anchor_y: f64) {
+        if self.zoom_anchor.is_some() {
+            self.set_zoom(zoom);
+            return;
+        }
+
+        let source_x = (anchor_x + self.view_x) / self.zoom_level;
+        let source_y = (anchor_y + self.view_y) / self.zoom_level;
+        self.set_zoom(zoom);
+        self.set_pan(
+            source_x * self.zoom_level - anchor_x,
+            source_y * self.zoom_level - anchor_y,
+        );
+    }
+
+    // View offset to render from. The stored offset keeps sub-pixel precision
+    // so drags accumulate smoothly; snapping only affects what is drawn. The
+    // samplers honor a fractional source origin, so without snapping the
+    // first source row and column can be cut short; snapped, every source
+    // pixel covers exactly `zoom` viewport pixels.
+    fn render_origin(&self) -> (f64, f64) {
+        let zoom = self.zoom_level;
+        if self.snap_pan_to_pixels && zoom >= 1.0 && zoom.fract() == 0.0 {
+            (
+                (self.view_x / zoom).round() * zoom,
+                (self.view_y / zoom).round() * zoom,
+            )
+        } else {
+            (self.view_x, self.view_y)
+        }
+    }
+
+    // Pan to the given view offset, kept within the scaled source so the
+    // viewport stays covered. When the scaled source is smaller than the
+    // viewport the pan locks to the origin and the image view centers it
+    // instead. Every change to the pan goes through here.
+    fn set_pan(&mut self, view_x: f64, view_y: f64) {
+        let (viewport_width, viewport_height) = self.viewport_size;
+        let max_x = (self.source_width as f64 * self.zoom_level - viewport_width).max(0.0);
+        let max_y = (self.source_height as f64 * self.zoom_level - viewport_height).max(0.0);
+        self.view_x = view_x.clamp(0.0, max_x);
+        self.view_y = view_y.clamp(0.0, max_y);
+    }
+
+    // Zoom so the whole source fits a view of the given size, and scroll back
+    // to the origin; the image view centers whatever doesn't fill the view.
+    // The zoom anchor is dropped, since the point it pinned has moved.
+    fn fit_to_view(&mut self, view_width: f64, view_height: f64) {
+        let zoom =
+            (view_width / self.source_width as f64).min(view_height / self.source_height as f64);
+        self.zoom_level = zoom.clamp(0.1, 10.0);
+        self.pinned_region = None;
+        self.zoom_anchor = None;
+        self.set_pan(0.0, 0.0);
+    }
+
+    // Derive zoom and pan from the pinned region, if any, so the region fills
+    // as much of a viewport of the given size as it can. The region is drawn
+    // from its top-left corner, since the view offset can't be negative.
+    fn frame_pinned_region(&mut self, viewport_width: f64, viewport_height: f64) {
+        if let Some((x, y, width, height)) = self.pinned_region {
+            if width <= 0.0 || height <= 0.0 {
+                return;
+            }
+
+            let zoom = (viewport_width / width)
+                .min(viewport_height / height)
+                .clamp(0.1, 10.0);
+            self.zoom_level = zoom;
+            self.set_pan(x * zoom, y * zoom);
+        }
+    }
+
+    // Source-space rectangle (x, y, width, height) drawn into a viewport of the
+    // given size, in source pixels with y increasing downwards. It is not
+    // clipped to the source, so parts past the edges are areas the viewport
+    // shows beyond the pattern.
+    fn visible_source_rect(
+        &self,
+        viewport_width: f64,
+        viewport_height: f64,
+    ) -> (f64, f64, f64, f64) {
+        let (view_x, view_y) = self.render_origin();
+        let zoom = self.zoom_level;
+
+        (
+            view_x / zoom,
+            view_y / zoom,
+            viewport_width / zoom,
+            viewport_height / zoom,
+        )
+    }
+
+    // Options for rendering the viewport from the cached pattern
+    fn render_options(&self) -> RenderOptions {
+        let fractional_zoom = self.zoom_level.fract() != 0.0;
+        let sampling = if self.smooth_checkerboard
+            && self.pattern_type == PatternType::Checkerboard
+            && fractional_zoom
+        {
+            SamplingMode::Supersampled
+        } else {
+            self.sampling
+        };
+
+        RenderOptions {
+            debug_borders: self.show_debug_borders
+                && (self.debug_borders_on_images
+                    || !matches!(self.pattern_type, PatternType::Image { .. })),
+            debug_border_inset: self.debug_border_inset,
+            blend_debug_corners: self.blend_debug_corners,
+            borders_only: self.borders_only,
+            rulers: self.show_rulers,
+            sampling,
+        }
+    }
+
+    // The tool that a click with these modifiers triggers, if any. Tools
+    // sharing a modifier resolve to the first in ModifierTool::ALL.
+    fn modifier_tool(&self, modifiers: NSEventModifierFlags) -> Option<ModifierTool> {
+        ModifierTool::ALL
+            .into_iter()
+            .zip(self.tool_modifiers)
+            .find(|(_, modifier)| modifiers.contains(modifier.flag()))
+            .map(|(tool, _)| tool)
+    }
+
+    // Filters to apply to the viewport, in order
+    fn active_filters(&self) -> Vec<ColorFilter> {
+        let mut filters = Vec::new();
+
+        if self.chroma_key_enabled {
+            filters.push(ColorFilter::ChromaKey {
+                color: self.chroma_key_color,
+                tolerance: self.chroma_key_tolerance,
+            });
+        }
+        if let Some(kind) = self.color_blind_simulation {
+            filters.push(ColorFilter::ColorBlind(kind));
+        }
+
+        filters
+    }
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        Self {
+            zoom_level: 1.0,
+            pattern_type: PatternType::Text,
+            view_x: 0.0,
+            view_y: 0.0,
+            zoom_anchor: None,
+            pinned_region: None,
+            viewport_size: (0.0, 0.0),
+            pan_direction: PanDirection::Grab,
+            pan_sensitivity: 1.0,
+            zoom_gesture_sensitivity: 1.0,
+            snap_pan_to_pixels: false,
+            remember_file_view_state: true,
+            source_width: 800,
+            source_height: 600,
+            file_name: None,
+            primary_text: Some("COMING SOON".to_string()),
+            secondary_text: None,
+            checker_colors: [[255, 255, 255, 255], [0, 0, 0, 255]],
+            smooth_checkerboard: false,
+            sampling: SamplingMode::Nearest,
+            show_debug_borders: true,
+            debug_border_inset: 0,
+            blend_debug_corners: false,
+            debug_borders_on_images: false,
+            text_scale: 1.0,
+            text_direction: TextDirection::LeftToRight,
+            view_background: ViewBackground::System,
+            tool_modifiers: ModifierTool::ALL.map(ModifierTool::default_modifier),
+            auto_hide_controls: true,
+            borders_only: false,
+            show_rulers: false,
+            frozen: false,
+            chroma_key_enabled: false,
+            chroma_key_color: [0, 255, 0],
+            chroma_key_tolerance: 32,
+            color_blind_simulation: None,
+        }
+    }
+}
+
+define_class!(
+    #[unsafe(super = NSObject)]
+    #[thread_kind = MainThreadOnly]
+    #[name = "AppDelegate"]
+    #[ivars = AppDelegateIvars]
+    struct AppDelegate;
+
+    unsafe impl NSObjectProtocol for AppDelegate {}
+
+    unsafe impl NSApplicationDelegate for AppDelegate {
+        #[unsafe(method(applicationDidFinishLaunching:))]
+        fn applicationDidFinishLaunching(&self, _notification: &NSNotification) {
+            println!("DEBUG: Application did finish launching");
+
+            let mtm = self.mtm();
+
+            let window = self.create_window(mtm);
+            let _ = self.ivars().window.set(window.clone());
+
+            window.setTitle(ns_string!("JP2 Viewer"));
+            window.center();
+
+            self.setup_image_view(&window, mtm);
+            self.setup_zoom_controls(&window, mtm);
+            self.setup_toolbar(&window, mtm);
+            self.setup_status_label(&window, mtm);
+            self.setup_mouse_handling(&window);
+            self.setup_main_menu(mtm);
+
+            // Initialize default state
+            {
+                let mut state = self.ivars().state.borrow_mut();
+                state.source_width = 800;
+                state.source_height = 600;
+                state.zoom_level = 1.0;
+                state.pattern_type = PatternType::Text;
+                state.primary_text = Some("COMING SOON".to_string());
+            }
+
+            // Apply persisted preferences on top of the defaults
+            self.load_preferences();
+            self.apply_view_background();
+
+            // Initialize the pattern cache
+            let _ = self.ensure_pattern_cache();
+
+            // Render initial UI
+            let _ = self.render_viewport();
+
+            // Give the image view keyboard focus so it receives shortcuts
+            if let Some(image_view) = self.ivars().image_view.get() {
+                let responder: &NSResponder = image_view;
+                window.makeFirstResponder(Some(responder));
+            }
+
+            // Activate app and make window visible
+            let app = NSApplication::sharedApplication(mtm);
+            unsafe { app.activate() };
+            window.makeKeyAndOrderFront(None);
+        }
+
+        #[unsafe(method(applicationWillTerminate:))]
+        fn applicationWillTerminate(&self, _notification: &NSNotification) {
+            // Quitting with ⌘Q doesn't close the window first
+            self.remember_view_state();
+        }
+    }
+
+    unsafe impl NSToolbarDelegate for AppDelegate {
+        #[unsafe(method_id(toolbar:itemForItemIdentifier:willBeInsertedIntoToolbar:))]
+        fn toolbar_itemForItemIdentifier_willBeInsertedIntoToolbar(
+            &self,
+            _toolbar: &NSToolbar,
+            item_identifier: &NSString,
+            _flag: bool,
+        ) -> Option<Retained<NSToolbarItem>> {
+            self.make_toolbar_item(item_identifier)
+        }
+
+        #[unsafe(method_id(toolbarDefaultItemIdentifiers:))]
+        fn toolbarDefaultItemIdentifiers(
+            &self,
+            _toolbar: &NSToolbar,
+        ) -> Retained<NSArray<NSString>> {
+            let mut identifiers: Vec<Retained<NSString>> = [
+                TOOLBAR_OPEN,
+                TOOLBAR_SAVE_IMAGE,
+                TOOLBAR_GRADIENT,
+                TOOLBAR_CHECKERBOARD,
+            ]
+            .iter()
+            .map(|id| NSString::from_str(id))
+            .collect();
+            identifiers.push(unsafe { NSToolbarFlexibleSpaceItemIdentifier }.copy());
+            identifiers.push(NSString::from_str(TOOLBAR_FIT));
+            identifiers.push(NSString::from_str(TOOLBAR_ACTUAL_SIZE));
+            identifiers.push(NSString::from_str(TOOLBAR_ZOOM_PRESETS));
+
+            NSArray::from_retained_slice(&identifiers)
+        }
+
+        #[unsafe(method_id(toolbarAllowedItemIdentifiers:))]
+        fn toolbarAllowedItemIdentifiers(
+            &self,
+            _toolbar: &NSToolbar,
+        ) -> Retained<NSArray<NSString>> {
+            let mut identifiers: Vec<Retained<NSString>> = TOOLBAR_ITEMS
+                .iter()
+                .map(|(id, _, _)| NSString::from_str(id))
+                .collect();
+            identifiers.push(unsafe { NSToolbarSpaceItemIdentifier }.copy());
+            identifiers.push(unsafe { NSToolbarFlexibleSpaceItemIdentifier }.copy());
+
+            NSArray::from_retained_slice(&identifiers)
+        }
+    }
+
+    unsafe impl NSWindowDelegate for AppDelegate {
+        #[unsafe(method(windowDidResize:))]
+        fn windowDidResize(&self, _notification: &NSNotification) {
+            // Re-frame a pinned region for the new view size, or just keep
+            // the image centered
+            if self.ivars().state.borrow().pinned_region.is_some() {
+                let _ = self.render_viewport();
+            } else {
+                self.center_image_view();
+            }
+        }
+
+        #[unsafe(method(windowWillClose:))]
+        fn windowWillClose(&self, _notification: &NSNotification) {
+            self.remember_view_state();
+
+            let mtm = self.mtm();
+            let app = NSApplication::sharedApplication(mtm);
+            unsafe { app.terminate(None) };
+        }
+    }
+
+    // Add custom methods for our delegate
+    impl AppDelegate {
+        #[unsafe(method(openFile:))]
+        fn openFile(&self, _sender: Option<&NSObject>) -> Bool {
+            println!("DEBUG: Opening file dialog");
+
+            let mtm = self.mtm();
+            let panel = unsafe { objc2_app_kit::NSOpenPanel::openPanel(mtm) };
+
+            unsafe {
+                panel.setCanChooseFiles(true);
                 panel.setCanChooseDirectories(false);
                 panel.setAllowsMultipleSelection(false);
 
-                // Use UTType to specify JP2 content type
-                let jp2_type = UTType::typeWithFilenameExtension(ns_string!("jp2"));
-                if let Some(jp2_type) = jp2_type {
-                    let allowed_types = NSArray::from_slice(&[&*jp2_type]);
-                    panel.setAllowedContentTypes(&allowed_types);
-                } else {
-                    println!("DEBUG: Failed to create UTType for JP2, allowing all files");
-                }
+                // Use UTType to restrict the panel to the formats that actually load
+                let content_types: Vec<_> = openable_extensions()
+                    .filter_map(|ext| UTType::typeWithFilenameExtension(&NSString::from_str(ext)))
+                    .collect();
+                if !content_types.is_empty() {
+                    let content_type_refs: Vec<_> = content_types.iter().map(|t| &**t).collect();
+                    let allowed_types = NSArray::from_slice(&content_type_refs);
+                    panel.setAllowedContentTypes(&allowed_types);
+                } else {
+                    println!("DEBUG: Failed to create UTTypes for image formats, allowing all files");
+                }
+
+                let response = panel.runModal();
+
+                if response == 1 {
+                    let urls = panel.URLs();
+                    if let Some(url) = urls.firstObject() {
+                        return self.open_url(&url);
+                    }
+                }
+            }
+
+            Bool::NO
+        }
+
+        #[unsafe(method(openURL:))]
+        fn openURL(&self, url: &NSURL) -> Bool {
+            self.open_url(url)
+        }
+
+        #[unsafe(method(saveImage:))]
+        fn saveImage(&self, _sender: Option<&NSObject>) -> Bool {
+            println!("DEBUG: Saving image");
+            self.save_image()
+        }
+
+        #[unsafe(method(createGradient:))]
+        fn createGradient(&self, _sender: Option<&NSObject>) -> Bool {
+            println!("DEBUG: Creating gradient image");
+
+            // Update state, leaving any opened file behind
+            self.ivars().state.borrow_mut().pattern_type = PatternType::Gradient;
+            self.clear_current_file();
+
+            // Full render (will regenerate pattern since type changed)
+            self.render_ui()
+        }
+
+        #[unsafe(method(createCheckerboard:))]
+        fn createCheckerboard(&self, _sender: Option<&NSObject>) -> Bool {
+            println!("DEBUG: Creating checkerboard image");
+
+            // Update state, leaving any opened file behind
+            self.ivars().state.borrow_mut().pattern_type = PatternType::Checkerboard;
+            self.clear_current_file();
+
+            // Full render (will regenerate pattern since type changed)
+            self.render_ui()
+        }
+
+        #[unsafe(method(revealInFinder:))]
+        fn revealInFinder(&self, _sender: Option<&NSObject>) -> Bool {
+            if let Some(url) = self.ivars().selected_file_path.borrow().as_deref() {
+                println!("DEBUG: Revealing in Finder: {:?}", url);
+
+                let urls = NSArray::from_slice(&[url]);
+                unsafe { NSWorkspace::sharedWorkspace().activateFileViewerSelectingURLs(&urls) };
+                return Bool::YES;
+            }
+
+            Bool::NO
+        }
+
+        #[unsafe(method(copyImagePath:))]
+        fn copyImagePath(&self, _sender: Option<&NSObject>) -> Bool {
+            let path = self
+                .ivars()
+                .selected_file_path
+                .borrow()
+                .as_deref()
+                .and_then(|url| unsafe { url.path() });
+
+            if let Some(path) = path {
+                unsafe {
+                    let pasteboard = NSPasteboard::generalPasteboard();
+                    pasteboard.clearContents();
+                    pasteboard.setString_forType(&path, NSPasteboardTypeString);
+                }
+                self.set_status(&format!("Copied {}", path));
+                Bool::YES
+            } else {
+                Bool::NO
+            }
+        }
+
+        #[unsafe(method(copyViewAsPDF:))]
+        fn copyViewAsPDF(&self, _sender: Option<&NSObject>) -> Bool {
+            let image_view = match self.ivars().image_view.get() {
+                Some(image_view) => image_view,
+                None => return Bool::NO,
+            };
+
+            // The image view already holds the rendered viewport, so its
+            // visible rectangle is exactly what's on screen at this zoom/pan
+            let data = unsafe {
+                let visible = image_view.visibleRect();
+                image_view.dataWithPDFInsideRect(visible)
+            };
+
+            unsafe {
+                let pasteboard = NSPasteboard::generalPasteboard();
+                pasteboard.clearContents();
+                pasteboard.setData_forType(Some(&data), NSPasteboardTypePDF);
+            }
+            self.set_status(&format!("Copied view as PDF ({} bytes)", data.len()));
+            Bool::YES
+        }
+
+        #[unsafe(method(showDominantColors:))]
+        fn showDominantColors(&self, _sender: Option<&NSObject>) -> Bool {
+            self.show_dominant_colors()
+        }
+
+        #[unsafe(method(measureSharpness:))]
+        fn measureSharpness(&self, _sender: Option<&NSObject>) -> Bool {
+            self.measure_sharpness()
+        }
+
+        #[unsafe(method(samplingButtonClicked:))]
+        fn samplingButtonClicked(&self, _sender: Option<&NSObject>) -> Bool {
+            self.toggle_sampling()
+        }
+
+        #[unsafe(method(exportRenderStats:))]
+        fn exportRenderStats(&self, _sender: Option<&NSObject>) -> Bool {
+            self.export_render_stats()
+        }
+
+        #[unsafe(method(copySwatchColor:))]
+        fn copySwatchColor(&self, sender: Option<&NSObject>) -> Bool {
+            if let Some(obj) = sender {
+                let hex: Retained<NSString> = unsafe { msg_send![obj, title] };
+                unsafe {
+                    let pasteboard = NSPasteboard::generalPasteboard();
+                    pasteboard.clearContents();
+                    pasteboard.setString_forType(&hex, NSPasteboardTypeString);
+                }
+                self.set_status(&format!("Copied {}", hex));
+                Bool::YES
+            } else {
+                Bool::NO
+            }
+        }
+
+        #[unsafe(method(validateMenuItem:))]
+        fn validateMenuItem(&self, item: &NSMenuItem) -> bool {
+            let action = unsafe { item.action() };
+
+            // View toggles show their current state as a checkmark
+            let checked = {
+                let state = self.ivars().state.borrow();
+                if action == Some(sel!(toggleRulers:)) {
+                    Some(state.show_rulers)
+                } else if action == Some(sel!(toggleBordersOnly:)) {
+                    Some(state.borders_only)
+                } else if action == Some(sel!(togglePinnedRegion:)) {
+                    Some(state.pinned_region.is_some())
+                } else if action == Some(sel!(toggleFreeze:)) {
+                    Some(state.frozen)
+                } else if action == Some(sel!(toggleHelpOverlay:)) {
+                    self.ivars().help_overlay.get().map(|overlay| unsafe { !overlay.isHidden() })
+                } else {
+                    None
+                }
+            };
+            if let Some(checked) = checked {
+                let state = if checked {
+                    NSControlStateValueOn
+                } else {
+                    NSControlStateValueOff
+                };
+                unsafe { item.setState(state) };
+            }
+
+            // File actions only make sense while an opened file is displayed
+            if action == Some(sel!(revealInFinder:)) || action == Some(sel!(copyImagePath:)) {
+                self.ivars().selected_file_path.borrow().is_some()
+            } else {
+                true
+            }
+        }
+
+        #[unsafe(method(checkerColorChanged:))]
+        fn checkerColorChanged(&self, sender: Option<&NSObject>) -> Bool {
+            if let Some(obj) = sender {
+                let index: isize = unsafe { msg_send![obj, tag] };
+                let color: Retained<NSColor> = unsafe { msg_send![obj, color] };
+                println!("DEBUG: Checker color {} changed to {:?}", index, color);
+
+                if let Some(rgba) = ns_color_to_rgba(&color) {
+                    return self.set_checker_color(index as usize, rgba);
+                }
+            }
+
+            Bool::NO
+        }
+
+        #[unsafe(method(debugBordersToggled:))]
+        fn debugBordersToggled(&self, sender: Option<&NSObject>) -> Bool {
+            if let Some(obj) = sender {
+                let button_state: isize = unsafe { msg_send![obj, state] };
+                println!("DEBUG: Debug borders toggled: {}", button_state);
+
+                self.ivars().state.borrow_mut().show_debug_borders =
+                    button_state == NSControlStateValueOn;
+                self.save_preferences();
+
+                // Borders are drawn at render time, so the pattern is reused
+                self.render_viewport()
+            } else {
+                Bool::NO
+            }
+        }
+
+        #[unsafe(method(blendDebugCornersToggled:))]
+        fn blendDebugCornersToggled(&self, sender: Option<&NSObject>) -> Bool {
+            if let Some(obj) = sender {
+                let button_state: isize = unsafe { msg_send![obj, state] };
+                println!("DEBUG: Blend debug corners toggled: {}", button_state);
+
+                self.ivars().state.borrow_mut().blend_debug_corners =
+                    button_state == NSControlStateValueOn;
+                self.save_preferences();
+
+                // Borders are drawn at render time, so the pattern is reused
+                self.render_viewport()
+            } else {
+                Bool::NO
+            }
+        }
+
+        #[unsafe(method(debugBordersOnImagesToggled:))]
+        fn debugBordersOnImagesToggled(&self, sender: Option<&NSObject>) -> Bool {
+            if let Some(obj) = sender {
+                let button_state: isize = unsafe { msg_send![obj, state] };
+                println!("DEBUG: Debug borders on images toggled: {}", button_state);
+
+                self.ivars().state.borrow_mut().debug_borders_on_images =
+                    button_state == NSControlStateValueOn;
+                self.save_preferences();
+
+                // Borders are drawn at render time, so the pattern is reused
+                self.render_viewport()
+            } else {
+                Bool::NO
+            }
+        }
+
+        #[unsafe(method(debugBorderInsetChanged:))]
+        fn debugBorderInsetChanged(&self, sender: Option<&NSObject>) -> Bool {
+            if let Some(obj) = sender {
+                let slider_value: f64 = unsafe { msg_send![obj, doubleValue] };
+                println!("DEBUG: Debug border inset changed to {}", slider_value);
+
+                self.ivars().state.borrow_mut().debug_border_inset =
+                    slider_value.round().clamp(0.0, MAX_DEBUG_BORDER_INSET as f64) as usize;
+                self.save_preferences();
+
+                self.render_viewport()
+            } else {
+                Bool::NO
+            }
+        }
+
+        #[unsafe(method(autoHideControlsToggled:))]
+        fn autoHideControlsToggled(&self, sender: Option<&NSObject>) -> Bool {
+            if let Some(obj) = sender {
+                let button_state: isize = unsafe { msg_send![obj, state] };
+                println!("DEBUG: Auto-hide controls toggled: {}", button_state);
+
+                self.ivars().state.borrow_mut().auto_hide_controls =
+                    button_state == NSControlStateValueOn;
+                self.save_preferences();
+                self.reset_idle_timer();
+
+                Bool::YES
+            } else {
+                Bool::NO
+            }
+        }
+
+        #[unsafe(method(smoothCheckerboardToggled:))]
+        fn smoothCheckerboardToggled(&self, sender: Option<&NSObject>) -> Bool {
+            if let Some(obj) = sender {
+                let button_state: isize = unsafe { msg_send![obj, state] };
+                println!("DEBUG: Smooth checkerboard toggled: {}", button_state);
+
+                self.ivars().state.borrow_mut().smooth_checkerboard =
+                    button_state == NSControlStateValueOn;
+                self.save_preferences();
+
+                // Sampling happens at render time, so the pattern is reused
+                self.render_viewport()
+            } else {
+                Bool::NO
+            }
+        }
+
+        #[unsafe(method(viewBackgroundChanged:))]
+        fn viewBackgroundChanged(&self, sender: Option<&NSObject>) -> Bool {
+            if let Some(obj) = sender {
+                let index: isize = unsafe { msg_send![obj, indexOfSelectedItem] };
+                println!("DEBUG: View background changed to item {}", index);
+
+                if let Some(background) = usize::try_from(index)
+                    .ok()
+                    .and_then(|index| ViewBackground::ALL.get(index))
+                {
+                    self.ivars().state.borrow_mut().view_background = *background;
+                    self.save_preferences();
+                    self.apply_view_background();
+                    return Bool::YES;
+                }
+            }
+
+            Bool::NO
+        }
+
+        #[unsafe(method(toolModifierChanged:))]
+        fn toolModifierChanged(&self, sender: Option<&NSObject>) -> Bool {
+            if let Some(obj) = sender {
+                let tool_index: isize = unsafe { msg_send![obj, tag] };
+                let index: isize = unsafe { msg_send![obj, indexOfSelectedItem] };
+                println!("DEBUG: Tool {} modifier changed to item {}", tool_index, index);
+
+                let modifier = usize::try_from(index)
+                    .ok()
+                    .and_then(|index| ToolModifier::ALL.get(index));
+                if let (Ok(tool_index), Some(modifier)) = (usize::try_from(tool_index), modifier) {
+                    if let Some(slot) = self
+                        .ivars()
+                        .state
+                        .borrow_mut()
+                        .tool_modifiers
+                        .get_mut(tool_index)
+                    {
+                        *slot = *modifier;
+                    }
+                    self.save_preferences();
+                    return Bool::YES;
+                }
+            }
+
+            Bool::NO
+        }
+
+        #[unsafe(method(textScaleChanged:))]
+        fn textScaleChanged(&self, sender: Option<&NSObject>) -> Bool {
+            if let Some(obj) = sender {
+                let slider_value: f64 = unsafe { msg_send![obj, doubleValue] };
+                println!("DEBUG: Text scale changed to {}", slider_value);
+
+                self.ivars().state.borrow_mut().text_scale = slider_value.clamp(0.25, 4.0);
+                self.save_preferences();
+
+                // Full render (will regenerate pattern since text size changed)
+                self.render_ui()
+            } else {
+                Bool::NO
+            }
+        }
+
+        #[unsafe(method(textDirectionChanged:))]
+        fn textDirectionChanged(&self, sender: Option<&NSObject>) -> Bool {
+            if let Some(obj) = sender {
+                let index: isize = unsafe { msg_send![obj, indexOfSelectedItem] };
+                println!("DEBUG: Text direction changed to item {}", index);
+
+                if let Some(direction) = usize::try_from(index)
+                    .ok()
+                    .and_then(|index| TextDirection::ALL.get(index))
+                {
+                    self.ivars().state.borrow_mut().text_direction = *direction;
+                    self.save_preferences();
+
+                    // Full render (will regenerate pattern since the layout changed)
+                    return self.render_ui();
+                }
+            }
+
+            Bool::NO
+        }
+
+        #[unsafe(method(panDirectionToggled:))]
+        fn panDirectionToggled(&self, sender: Option<&NSObject>) -> Bool {
+            if let Some(obj) = sender {
+                let button_state: isize = unsafe { msg_send![obj, state] };
+                println!("DEBUG: Scroll-style panning toggled: {}", button_state);
+
+                self.ivars().state.borrow_mut().pan_direction =
+                    if button_state == NSControlStateValueOn {
+                        PanDirection::Scroll
+                    } else {
+                        PanDirection::Grab
+                    };
+                self.save_preferences();
+
+                Bool::YES
+            } else {
+                Bool::NO
+            }
+        }
+
+        #[unsafe(method(zoomToFit:))]
+        fn zoomToFit(&self, _sender: Option<&NSObject>) -> Bool {
+            println!("DEBUG: Zoom to fit");
+            self.zoom_to_fit()
+        }
+
+        #[unsafe(method(actualSize:))]
+        fn actualSize(&self, _sender: Option<&NSObject>) -> Bool {
+            println!("DEBUG: Actual size");
+            self.zoom_to_actual_size()
+        }
+
+        #[unsafe(method(toggleRulers:))]
+        fn toggleRulers(&self, _sender: Option<&NSObject>) -> Bool {
+            self.toggle_rulers()
+        }
+
+        #[unsafe(method(toggleBordersOnly:))]
+        fn toggleBordersOnly(&self, _sender: Option<&NSObject>) -> Bool {
+            self.toggle_borders_only()
+        }
+
+        #[unsafe(method(togglePinnedRegion:))]
+        fn togglePinnedRegion(&self, _sender: Option<&NSObject>) -> Bool {
+            self.toggle_pinned_region()
+        }
+
+        #[unsafe(method(toggleFreeze:))]
+        fn toggleFreeze(&self, _sender: Option<&NSObject>) -> Bool {
+            self.toggle_freeze()
+        }
+
+        #[unsafe(method(toggleHelpOverlay:))]
+        fn toggleHelpOverlay(&self, _sender: Option<&NSObject>) -> Bool {
+            self.toggle_help_overlay()
+        }
+
+        #[unsafe(method(zoomPresetSelected:))]
+        fn zoomPresetSelected(&self, sender: Option<&NSObject>) -> Bool {
+            if let Some(obj) = sender {
+                let index: isize = unsafe { msg_send![obj, indexOfSelectedItem] };
+                match ZOOM_PRESETS.get(index as usize) {
+                    Some(&zoom) => self.zoom_to_preset(zoom),
+                    None => Bool::NO,
+                }
+            } else {
+                Bool::NO
+            }
+        }
+
+        #[unsafe(method(rememberFileViewStateToggled:))]
+        fn rememberFileViewStateToggled(&self, sender: Option<&NSObject>) -> Bool {
+            if let Some(obj) = sender {
+                let button_state: isize = unsafe { msg_send![obj, state] };
+                println!("DEBUG: Remember file view state toggled: {}", button_state);
+
+                self.ivars().state.borrow_mut().remember_file_view_state =
+                    button_state == NSControlStateValueOn;
+                self.save_preferences();
+                Bool::YES
+            } else {
+                Bool::NO
+            }
+        }
+
+        #[unsafe(method(snapPanToggled:))]
+        fn snapPanToggled(&self, sender: Option<&NSObject>) -> Bool {
+            if let Some(obj) = sender {
+                let button_state: isize = unsafe { msg_send![obj, state] };
+                println!("DEBUG: Snap pan to pixels toggled: {}", button_state);
+
+                self.ivars().state.borrow_mut().snap_pan_to_pixels =
+                    button_state == NSControlStateValueOn;
+                self.save_preferences();
+
+                // View-only change, the pattern doesn't need regenerating
+                self.render_viewport()
+            } else {
+                Bool::NO
+            }
+        }
+
+        #[unsafe(method(panSensitivityChanged:))]
+        fn panSensitivityChanged(&self, sender: Option<&NSObject>) -> Bool {
+            if let Some(obj) = sender {
+                let slider_value: f64 = unsafe { msg_send![obj, doubleValue] };
+                println!("DEBUG: Pan sensitivity changed to {}", slider_value);
+
+                self.ivars().state.borrow_mut().pan_sensitivity = slider_value.clamp(0.25, 4.0);
+                self.save_preferences();
+
+                Bool::YES
+            } else {
+                Bool::NO
+            }
+        }
+
+        #[unsafe(method(zoomGestureSensitivityChanged:))]
+        fn zoomGestureSensitivityChanged(&self, sender: Option<&NSObject>) -> Bool {
+            if let Some(obj) = sender {
+                let slider_value: f64 = unsafe { msg_send![obj, doubleValue] };
+                println!("DEBUG: Zoom gesture sensitivity changed to {}", slider_value);
+
+                self.ivars().state.borrow_mut().zoom_gesture_sensitivity =
+                    slider_value.clamp(0.25, 4.0);
+                self.save_preferences();
+
+                Bool::YES
+            } else {
+                Bool::NO
+            }
+        }
+
+        #[unsafe(method(chromaKeyToggled:))]
+        fn chromaKeyToggled(&self, sender: Option<&NSObject>) -> Bool {
+            if let Some(obj) = sender {
+                let button_state: isize = unsafe { msg_send![obj, state] };
+                println!("DEBUG: Chroma key toggled: {}", button_state);
+
+                self.ivars().state.borrow_mut().chroma_key_enabled =
+                    button_state == NSControlStateValueOn;
+
+                // Filters are applied per viewport render, no regeneration needed
+                self.render_viewport()
+            } else {
+                Bool::NO
+            }
+        }
+
+        #[unsafe(method(chromaKeyColorChanged:))]
+        fn chromaKeyColorChanged(&self, sender: Option<&NSObject>) -> Bool {
+            if let Some(obj) = sender {
+                let color: Retained<NSColor> = unsafe { msg_send![obj, color] };
+                println!("DEBUG: Chroma key color changed to {:?}", color);
+
+                if let Some([r, g, b, _]) = ns_color_to_rgba(&color) {
+                    self.ivars().state.borrow_mut().chroma_key_color = [r, g, b];
+                    return self.render_viewport();
+                }
+            }
+
+            Bool::NO
+        }
+
+        #[unsafe(method(chromaKeyToleranceChanged:))]
+        fn chromaKeyToleranceChanged(&self, sender: Option<&NSObject>) -> Bool {
+            if let Some(obj) = sender {
+                let slider_value: f64 = unsafe { msg_send![obj, doubleValue] };
+                println!("DEBUG: Chroma key tolerance changed to {}", slider_value);
+
+                self.ivars().state.borrow_mut().chroma_key_tolerance =
+                    slider_value.round().clamp(0.0, 255.0) as u8;
+                self.render_viewport()
+            } else {
+                Bool::NO
+            }
+        }
+
+        #[unsafe(method(colorBlindSimulationChanged:))]
+        fn colorBlindSimulationChanged(&self, sender: Option<&NSObject>) -> Bool {
+            if let Some(obj) = sender {
+                let index: isize = unsafe { msg_send![obj, indexOfSelectedItem] };
+                println!("DEBUG: Color blindness simulation changed to item {}", index);
+
+                // Item 0 is "Off"; the rest follow ColorBlindType::ALL
+                self.ivars().state.borrow_mut().color_blind_simulation = usize::try_from(index - 1)
+                    .ok()
+                    .and_then(|index| ColorBlindType::ALL.get(index))
+                    .copied();
+                self.render_viewport()
+            } else {
+                Bool::NO
+            }
+        }
+
+        #[unsafe(method(showPreferences:))]
+        fn showPreferences(&self, _sender: Option<&NSObject>) -> Bool {
+            self.show_preferences()
+        }
+
+        #[unsafe(method(zoomChanged:))]
+        fn zoomChanged(&self, sender: Option<&NSObject>) -> Bool {
+            if let Some(obj) = sender {
+                let slider_value: f64 = unsafe { msg_send![obj, doubleValue] };
+                println!("DEBUG: Zoom changed to {}", slider_value);
+
+                // Keep whatever is in the middle of the view in place
+                let (anchor_x, anchor_y) = self.visible_center_point().unwrap_or((0.0, 0.0));
+                self.ivars()
+                    .state
+                    .borrow_mut()
+                    .set_zoom_about_point(slider_value, anchor_x, anchor_y);
+
+                // Only render the viewport (not regenerate pattern)
+                self.render_viewport()
+            } else {
+                Bool::NO
+            }
+        }
+
+        #[unsafe(method(keyDown:))]
+        fn keyDown(&self, event: &NSEvent) -> Bool {
+            let key = unsafe { event.charactersIgnoringModifiers() }
+                .map(|characters| characters.to_string())
+                .unwrap_or_default();
+            let modifiers = unsafe { event.modifierFlags() };
+            let command = modifiers.contains(NSEventModifierFlags::Command);
+            let option = modifiers.contains(NSEventModifierFlags::Option);
+            println!(
+                "DEBUG: Key down: {:?} (command: {}, option: {})",
+                key, command, option
+            );
+
+            match shortcut_for(&key, command, option).map(|shortcut| shortcut.action) {
+                Some(ShortcutAction::SaveScreenshot) => self.save_screenshot_to_desktop(),
+                Some(ShortcutAction::ToggleBordersOnly) => self.toggle_borders_only(),
+                Some(ShortcutAction::ToggleFreeze) => self.toggle_freeze(),
+                Some(ShortcutAction::Print) => self.print_image(false),
+                Some(ShortcutAction::PrintActualSize) => self.print_image(true),
+                Some(ShortcutAction::ZoomInPreset) => {
+                    let zoom = next_zoom_preset(self.ivars().state.borrow().zoom_level);
+                    self.zoom_to_preset(zoom)
+                }
+                Some(ShortcutAction::ZoomOutPreset) => {
+                    let zoom = previous_zoom_preset(self.ivars().state.borrow().zoom_level);
+                    self.zoom_to_preset(zoom)
+                }
+                Some(ShortcutAction::ToggleRulers) => self.toggle_rulers(),
+                Some(ShortcutAction::TogglePinnedRegion) => self.toggle_pinned_region(),
+                Some(ShortcutAction::ToggleSampling) => self.toggle_sampling(),
+                Some(ShortcutAction::ShowDominantColors) => self.show_dominant_colors(),
+                Some(ShortcutAction::ToggleHelp) => self.toggle_help_overlay(),
+                Some(ShortcutAction::NudgeUp) => self.nudge_pan(0.0, -1.0),
+                Some(ShortcutAction::NudgeDown) => self.nudge_pan(0.0, 1.0),
+                Some(ShortcutAction::NudgeLeft) => self.nudge_pan(-1.0, 0.0),
+                Some(ShortcutAction::NudgeRight) => self.nudge_pan(1.0, 0.0),
+                None => Bool::NO,
+            }
+        }
+
+        #[unsafe(method(mouseDown:))]
+        fn mouseDown(&self, event: &NSEvent) -> Bool {
+            println!("DEBUG: Mouse down received");
+            let location = unsafe { event.locationInWindow() };
+
+            // Modifier-clicks trigger tools instead of starting a pan
+            let modifiers = unsafe { event.modifierFlags() };
+            let tool = self.ivars().state.borrow().modifier_tool(modifiers);
+            match tool {
+                Some(ModifierTool::ZoomAnchor) => {
+                    if let Some((source_x, source_y)) = self.window_to_source(location) {
+                        self.ivars().state.borrow_mut().zoom_anchor = Some((source_x, source_y));
+                        self.set_status(&format!(
+                            "Zoom anchor set at ({:.0}, {:.0})",
+                            source_x, source_y
+                        ));
+                    }
+                    return Bool::YES;
+                }
+                None => {}
+            }
+
+            *self.ivars().is_panning.borrow_mut() = true;
+            *self.ivars().last_mouse_location.borrow_mut() = location;
+
+            Bool::YES
+        }
+
+        #[unsafe(method(mouseDragged:))]
+        fn mouseDragged(&self, event: &NSEvent) -> Bool {
+            println!("DEBUG: Mouse dragged");
+            if *self.ivars().is_panning.borrow() {
+                let current_location = unsafe { event.locationInWindow() };
+                let last_location = *self.ivars().last_mouse_location.borrow();
+
+                // Window coordinates grow upwards while image rows grow downwards,
+                // so flip the vertical delta into image space. Image pixels are
+                // backing pixels, so scale the delta from points.
+                let scale = self.backing_scale();
+                let delta_x = (current_location.x - last_location.x) * scale;
+                let delta_y = (last_location.y - current_location.y) * scale;
+
+                // Update state. view_x/view_y are in zoomed viewport pixels, so the
+                // screen delta maps 1:1 at every zoom level before sensitivity.
+                {
+                    let mut state = self.ivars().state.borrow_mut();
+                    state.zoom_anchor = None;
+                    state.pinned_region = None;
+
+                    let delta_x = delta_x * state.pan_sensitivity;
+                    let delta_y = delta_y * state.pan_sensitivity;
+                    let (view_x, view_y) = match state.pan_direction {
+                        PanDirection::Grab => (state.view_x - delta_x, state.view_y - delta_y),
+                        PanDirection::Scroll => (state.view_x + delta_x, state.view_y + delta_y),
+                    };
+                    state.set_pan(view_x, view_y);
+                }
+
+                // Only render the viewport (not regenerate pattern)
+                let _ = self.render_viewport();
+
+                *self.ivars().last_mouse_location.borrow_mut() = current_location;
+                return Bool::YES;
+            }
+
+            Bool::NO
+        }
+
+        #[unsafe(method(mouseUp:))]
+        fn mouseUp(&self, _event: &NSEvent) -> Bool {
+            println!("DEBUG: Mouse up received");
+            *self.ivars().is_panning.borrow_mut() = false;
+            Bool::YES
+        }
+
+        #[unsafe(method(mouseMoved:))]
+        fn mouseMoved(&self, _event: &NSEvent) -> Bool {
+            self.reset_idle_timer();
+            Bool::YES
+        }
+
+        #[unsafe(method(hideIdleControls:))]
+        fn hideIdleControls(&self, _timer: &NSTimer) {
+            self.ivars().idle_timer.borrow_mut().take();
+
+            if self.ivars().state.borrow().auto_hide_controls && self.is_full_screen() {
+                println!("DEBUG: Hiding idle controls");
+                self.set_controls_visible(false);
+            }
+        }
+
+        #[unsafe(method(checkRenderStats:))]
+        fn checkRenderStats(&self, timer: &NSTimer) {
+            let message = match self.ivars().render_stats_job.borrow().as_ref().map(|job| job.try_recv()) {
+                Some(Err(mpsc::TryRecvError::Empty)) => return,
+                Some(Ok(message)) => message,
+                Some(Err(mpsc::TryRecvError::Disconnected)) | None => {
+                    "Measuring render stats failed".to_string()
+                }
+            };
+
+            unsafe { timer.invalidate() };
+            self.ivars().render_stats_job.borrow_mut().take();
+            self.set_status(&message);
+        }
+
+        // Zoom about the cursor on Command-scroll, and pan on any other
+        // scroll. Only the visible part of the image is rendered, so the
+        // scroll view has nothing to scroll and panning moves the view offset.
+        #[unsafe(method(scrollWheel:))]
+        fn scrollWheel(&self, event: &NSEvent) -> Bool {
+            let modifiers = unsafe { event.modifierFlags() };
+            let (delta_x, delta, precise) = unsafe {
+                (
+                    event.scrollingDeltaX(),
+                    event.scrollingDeltaY(),
+                    event.hasPreciseScrollingDeltas(),
+                )
+            };
+            if !modifiers.contains(NSEventModifierFlags::Command) {
+                // Trackpads report deltas in points, wheels in lines. Content
+                // follows the scroll the way it would in a scroll view.
+                let step = if precise { 1.0 } else { SCROLL_LINE_POINTS };
+                let scale = self.backing_scale();
+                {
+                    let mut state = self.ivars().state.borrow_mut();
+                    state.zoom_anchor = None;
+                    state.pinned_region = None;
+                    let view_x = state.view_x - delta_x * step * scale;
+                    let view_y = state.view_y - delta * step * scale;
+                    state.set_pan(view_x, view_y);
+                }
+                return self.render_viewport();
+            }
+
+            if delta == 0.0 {
+                return Bool::YES;
+            }
+
+            // Trackpads report deltas in points, wheels in lines; either way a
+            // scroll up zooms in about the cursor
+            let step = if precise { 0.005 } else { 0.1 };
+            let location = unsafe { event.locationInWindow() };
+            let Some((anchor_x, anchor_y)) = self.window_to_image_point(location) else {
+                return Bool::NO;
+            };
+            {
+                let mut state = self.ivars().state.borrow_mut();
+                let zoom = state.zoom_level * (delta * step * state.zoom_gesture_sensitivity).exp();
+                state.set_zoom_about_point(zoom, anchor_x, anchor_y);
+            }
+
+            self.render_viewport()
+        }
+
+        #[unsafe(method(handlePinchGesture:))]
+        fn handlePinchGesture(&self, sender: Option<&NSObject>) -> Bool {
+            if let Some(recognizer) = sender {
+                unsafe {
+                    let state: isize = msg_send![recognizer, state];
+
+                    // Handle different gesture states
+                    if state == 1 { // GSBegan (1)
+                        println!("DEBUG: Pinch gesture began");
+
+                        // Store current zoom level as base for this gesture sequence
+                        *self.ivars().base_zoom_level.borrow_mut() = self.ivars().state.borrow().zoom_level;
+                    }
+
+                    // Get the magnification factor from the gesture recognizer
+                    let magnification: f64 = msg_send![recognizer, magnification];
+                    println!("DEBUG: Pinch magnification: {}", magnification);
+
+                    // Apply zoom change based on the base zoom level and magnification,
+                    // scaled by the user's gesture sensitivity
+                    let base_zoom = *self.ivars().base_zoom_level.borrow();
+                    let sensitivity = self.ivars().state.borrow().zoom_gesture_sensitivity;
+                    let new_zoom = base_zoom * (1.0 + magnification).max(0.01).powf(sensitivity);
+
+                    // Update state with new zoom level, keeping the point between
+                    // the fingers in place
+                    let location: NSPoint = msg_send![recognizer, locationInView: None::<&NSView>];
+                    let (anchor_x, anchor_y) =
+                        self.window_to_image_point(location).unwrap_or((0.0, 0.0));
+                    self.ivars()
+                        .state
+                        .borrow_mut()
+                        .set_zoom_about_point(new_zoom, anchor_x, anchor_y);
+
+                    // Only render the viewport (not regenerate pattern)
+                    return self.render_viewport();
+                }
+            }
+
+            Bool::NO
+        }
+    }
+);
+
+// Implement custom methods for AppDelegate
+impl AppDelegate {
+    fn new(mtm: MainThreadMarker) -> Retained<Self> {
+        let ivars = AppDelegateIvars {
+            base_zoom_level: RefCell::new(1.0),
+            state: RefCell::new(AppState::default()),
+            cached_pattern: RefCell::new(None),
+            ..Default::default()
+        };
+        let this = Self::alloc(mtm).set_ivars(ivars);
+        unsafe { msg_send![super(this), init] }
+    }
+
+    fn create_window(&self, mtm: MainThreadMarker) -> Retained<NSWindow> {
+        let window_frame = NSRect::new(NSPoint::new(100., 100.), NSSize::new(800., 600.));
+        let style = NSWindowStyleMask::Titled
+            | NSWindowStyleMask::Closable
+            | NSWindowStyleMask::Resizable
+            | NSWindowStyleMask::Miniaturizable;
+
+        let window = unsafe {
+            NSWindow::initWithContentRect_styleMask_backing_defer(
+                NSWindow::alloc(mtm),
+                window_frame,
+                style,
+                NSBackingStoreType::Buffered,
+                false,
+            )
+        };
+
+        // Important: prevent automatic closing from releasing the window
+        // This is needed when not using a window controller
+        unsafe { window.setReleasedWhenClosed(false) };
+
+        // Keep the window large enough for the fixed-position controls
+        unsafe { window.setContentMinSize(MIN_CONTENT_SIZE) };
+
+        window
+    }
+
+    fn setup_image_view(&self, window: &NSWindow, mtm: MainThreadMarker) {
+        let content_view = window.contentView().unwrap();
+        let content_frame = content_view.bounds();
+
+        // Calculate the main view frame, leaving room for controls at the bottom
+        let main_view_frame = main_view_frame(content_frame, CONTROLS_HEIGHT);
+
+        // Create a scroll view
+        let scroll_view =
+            unsafe { NSScrollView::initWithFrame(NSScrollView::alloc(mtm), main_view_frame) };
+
+        unsafe {
+            // The rendered image never outgrows the visible area; panning
+            // moves the view offset instead of scrolling
+            scroll_view.setHasVerticalScroller(false);
+            scroll_view.setHasHorizontalScroller(false);
+            scroll_view.setAutoresizingMask(
+                NSAutoresizingMaskOptions::ViewWidthSizable
+                    | NSAutoresizingMaskOptions::ViewHeightSizable,
+            );
+
+            // Create our custom image view for the document view
+            let frame = NSRect::ZERO;
+            let new_image_view = CustomImageView::new(mtm, frame);
+
+            // The view's frame always follows the rendered image (zooming
+            // happens when the viewport is rendered), so it's drawn 1:1 and
+            // never resampled
+            new_image_view.setImageScaling(NSImageScaling::ScaleNone);
+
+            // Accept files dropped from the Finder
+            new_image_view
+                .registerForDraggedTypes(&NSArray::from_slice(&[NSPasteboardTypeFileURL]));
+
+            // Create and configure the magnification gesture recognizer for pinch-to-zoom
+            let recognizer = NSMagnificationGestureRecognizer::alloc(mtm);
+            let recognizer: Retained<NSMagnificationGestureRecognizer> =
+                msg_send![recognizer, init];
+
+            // Set the action and target for the gesture recognizer
+            recognizer.setAction(Some(sel!(handlePinchGesture:)));
+            let target: Option<&AnyObject> = Some(self.as_ref());
+            recognizer.setTarget(target);
+
+            // Add the gesture recognizer to the image view
+            let view_ref: &AnyObject = new_image_view.as_ref();
+            let _: () = msg_send![view_ref, addGestureRecognizer: &*recognizer];
+
+            // Store the gesture recognizer
+            let _ = self.ivars().magnification_recognizer.set(recognizer);
+
+            // Attach the right-click menu
+            new_image_view.setMenu(Some(&self.create_context_menu(mtm)));
+
+            // Set the image view as the document view
+            scroll_view.setDocumentView(Some(&*new_image_view));
+
+            // Add the scroll view to the content view
+            content_view.addSubview(&scroll_view);
+
+            // Store the views
+            let _ = self.ivars().scroll_view.set(scroll_view.clone());
+            let _ = self.ivars().image_view.set(new_image_view.clone());
+        }
+    }
+
+    fn setup_zoom_controls(&self, window: &NSWindow, mtm: MainThreadMarker) {
+        let content_view = window.contentView().unwrap();
+
+        // Create a slider for zoom control
+        let slider_frame = NSRect::new(NSPoint::new(20., 25.), NSSize::new(180., 30.));
+        let slider = unsafe { NSSlider::initWithFrame(NSSlider::alloc(mtm), slider_frame) };
+
+        unsafe {
+            // Configure slider properties
+            slider.setMinValue(0.1);
+            slider.setMaxValue(5.0);
+            slider.setDoubleValue(1.0);
+
+            // Set number of tick marks directly using msg_send - use i64 (long) instead of i32
+            let _: () = msg_send![&*slider, setNumberOfTickMarks: 9i64];
+            let _: () = msg_send![&*slider, setAllowsTickMarkValuesOnly: false];
+
+            // Set action and target
+            slider.setAction(Some(sel!(zoomChanged:)));
+            let target: Option<&AnyObject> = Some(self.as_ref());
+            slider.setTarget(target);
+
+            // Add to content view
+            content_view.addSubview(&slider);
+
+            // Store the slider
+            let _ = self.ivars().zoom_slider.set(slider.clone());
+
+            // Sampling toggle to the right of the slider, for comparing modes
+            let button = NSButton::buttonWithTitle_target_action(
+                &NSString::from_str(self.ivars().state.borrow().sampling.button_title()),
+                target,
+                Some(sel!(samplingButtonClicked:)),
+                mtm,
+            );
+            button.setFrame(NSRect::new(NSPoint::new(210., 27.), NSSize::new(120., 26.)));
+            content_view.addSubview(&button);
+            let _ = self.ivars().sampling_button.set(button);
+
+            // One image pixel per screen pixel
+            let button = NSButton::buttonWithTitle_target_action(
+                ns_string!("1:1"),
+                target,
+                Some(sel!(actualSize:)),
+                mtm,
+            );
+            button.setFrame(NSRect::new(NSPoint::new(340., 27.), NSSize::new(50., 26.)));
+            content_view.addSubview(&button);
+            let _ = self.ivars().actual_size_button.set(button);
+        }
+    }
+
+    // Attach the main toolbar. Items are created on demand by the
+    // NSToolbarDelegate implementation from the identifiers in TOOLBAR_ITEMS.
+    fn setup_toolbar(&self, window: &NSWindow, mtm: MainThreadMarker) {
+        unsafe {
+            let toolbar =
+                NSToolbar::initWithIdentifier(NSToolbar::alloc(mtm), ns_string!("MainToolbar"));
+            toolbar.setDelegate(Some(ProtocolObject::from_ref(self)));
+            toolbar.setDisplayMode(NSToolbarDisplayMode::IconAndLabel);
+            toolbar.setAllowsUserCustomization(true);
+            toolbar.setAutosavesConfiguration(true);
+
+            window.setToolbar(Some(&toolbar));
+        }
+    }
+
+    // Build the toolbar item for one of the identifiers in TOOLBAR_ITEMS
+    fn make_toolbar_item(&self, identifier: &NSString) -> Option<Retained<NSToolbarItem>> {
+        let identifier_str = identifier.to_string();
+        let &(_, label, symbol) = TOOLBAR_ITEMS
+            .iter()
+            .find(|(id, _, _)| *id == identifier_str)?;
+
+        if identifier_str == TOOLBAR_ZOOM_PRESETS {
+            return Some(self.make_zoom_presets_item(identifier, label));
+        }
+
+        let action = match identifier_str.as_str() {
+            TOOLBAR_OPEN => sel!(openFile:),
+            TOOLBAR_SAVE_IMAGE => sel!(saveImage:),
+            TOOLBAR_GRADIENT => sel!(createGradient:),
+            TOOLBAR_CHECKERBOARD => sel!(createCheckerboard:),
+            TOOLBAR_FIT => sel!(zoomToFit:),
+            TOOLBAR_ACTUAL_SIZE => sel!(actualSize:),
+            _ => return None,
+        };
+
+        let mtm = self.mtm();
+        let label = NSString::from_str(label);
+
+        unsafe {
+            let item = NSToolbarItem::initWithItemIdentifier(NSToolbarItem::alloc(mtm), identifier);
+            item.setLabel(&label);
+            item.setPaletteLabel(&label);
+            item.setToolTip(Some(&label));
+            item.setImage(
+                NSImage::imageWithSystemSymbolName_accessibilityDescription(
+                    &NSString::from_str(symbol),
+                    Some(&label),
+                )
+                .as_deref(),
+            );
+            item.setBordered(true);
+            item.setAction(Some(action));
+
+            let target: Option<&AnyObject> = Some(self.as_ref());
+            item.setTarget(target);
+
+            Some(item)
+        }
+    }
+
+    // Toolbar item holding a popup of ZOOM_PRESETS
+    fn make_zoom_presets_item(
+        &self,
+        identifier: &NSString,
+        label: &str,
+    ) -> Retained<NSToolbarItem> {
+        let mtm = self.mtm();
+        let label = NSString::from_str(label);
+
+        unsafe {
+            let popup_frame = NSRect::new(NSPoint::new(0., 0.), NSSize::new(80., 24.));
+            let popup = NSPopUpButton::initWithFrame_pullsDown(
+                NSPopUpButton::alloc(mtm),
+                popup_frame,
+                false,
+            );
+            for preset in ZOOM_PRESETS {
+                popup.addItemWithTitle(&NSString::from_str(&format!("{}%", preset * 100.0)));
+            }
+            popup.setAction(Some(sel!(zoomPresetSelected:)));
+            let target: Option<&AnyObject> = Some(self.as_ref());
+            popup.setTarget(target);
+
+            let item = NSToolbarItem::initWithItemIdentifier(NSToolbarItem::alloc(mtm), identifier);
+            item.setLabel(&label);
+            item.setPaletteLabel(&label);
+            item.setToolTip(Some(&label));
+            item.setView(Some(&popup));
+
+            *self.ivars().zoom_preset_popup.borrow_mut() = Some(popup);
+            self.sync_zoom_preset_popup();
+
+            item
+        }
+    }
+
+    // Select the popup entry matching the current zoom, or nothing when the
+    // zoom is between presets
+    fn sync_zoom_preset_popup(&self) {
+        if let Some(popup) = self.ivars().zoom_preset_popup.borrow().as_deref() {
+            let zoom = self.ivars().state.borrow().zoom_level;
+            let index = ZOOM_PRESETS
+                .iter()
+                .position(|preset| (preset - zoom).abs() < 1e-6)
+                .map_or(-1, |index| index as isize);
+            unsafe { popup.selectItemAtIndex(index) };
+        }
+    }
+
+    // Zoom to one of ZOOM_PRESETS, keeping any zoom anchor in place
+    fn zoom_to_preset(&self, zoom: f64) -> Bool {
+        println!("DEBUG: Zoom preset {}", zoom);
+        self.ivars().state.borrow_mut().set_zoom(zoom);
+        self.render_viewport()
+    }
+
+    // Paint the scroll view, which shows around the image and through its
+    // transparent pixels, in the chosen background color
+    fn apply_view_background(&self) {
+        if let Some(scroll_view) = self.ivars().scroll_view.get() {
+            let background = self.ivars().state.borrow().view_background;
+            unsafe {
+                scroll_view.setDrawsBackground(true);
+                scroll_view.setBackgroundColor(&background.color());
+            }
+        }
+    }
+
+    // Zoom so the whole source pattern fits in the scroll view's visible area
+    fn zoom_to_fit(&self) -> Bool {
+        let Some((width, height)) = self.viewport_size() else {
+            return Bool::NO;
+        };
+
+        self.ivars().state.borrow_mut().fit_to_view(width, height);
+
+        self.render_viewport()
+    }
+
+    // Reset to 100%, scrolled to the origin. Rendered images are sized for
+    // the backing scale factor, so this is one source pixel per backing pixel.
+    fn zoom_to_actual_size(&self) -> Bool {
+        {
+            let mut state = self.ivars().state.borrow_mut();
+            state.zoom_level = 1.0;
+            state.pinned_region = None;
+            state.set_pan(0.0, 0.0);
+        }
+
+        self.render_viewport()
+    }
+
+    // Show or hide the keyboard shortcut overlay, creating it on first use
+    fn toggle_help_overlay(&self) -> Bool {
+        let mtm = self.mtm();
+        let overlay = self
+            .ivars()
+            .help_overlay
+            .get_or_init(|| self.create_help_overlay(mtm));
+        overlay.setHidden(unsafe { !overlay.isHidden() });
+
+        Bool::YES
+    }
+
+    // Build the (initially hidden) shortcut list, centered over the image area
+    fn create_help_overlay(&self, mtm: MainThreadMarker) -> Retained<NSTextField> {
+        let text = NSString::from_str(&shortcut_help_text());
+        let overlay = unsafe { NSTextField::labelWithString(&text, mtm) };
+
+        unsafe {
+            overlay.setFont(NSFont::userFixedPitchFontOfSize(13.0).as_deref());
+            overlay.setTextColor(Some(&NSColor::whiteColor()));
+            overlay.setDrawsBackground(true);
+            overlay.setBackgroundColor(Some(&NSColor::colorWithWhite_alpha(0.0, 0.75)));
+            overlay.sizeToFit();
+            overlay.setHidden(true);
+
+            if let Some(scroll_view) = self.ivars().scroll_view.get() {
+                let area = scroll_view.frame();
+                let size = overlay.frame().size;
+                overlay.setFrameOrigin(NSPoint::new(
+                    area.origin.x + ((area.size.width - size.width) / 2.0).max(0.0),
+                    area.origin.y + ((area.size.height - size.height) / 2.0).max(0.0),
+                ));
+                overlay.setAutoresizingMask(
+                    NSAutoresizingMaskOptions::ViewMinXMargin
+                        | NSAutoresizingMaskOptions::ViewMaxXMargin
+                        | NSAutoresizingMaskOptions::ViewMinYMargin
+                        | NSAutoresizingMaskOptions::ViewMaxYMargin,
+                );
+
+                // Added last so it draws above the scroll view
+                if let Some(content_view) = self.ivars().window.get().and_then(|w| w.contentView())
+                {
+                    content_view.addSubview(&overlay);
+                }
+            }
+        }
+
+        overlay
+    }
+
+    // Bring up the preferences window, creating it on first use
+    fn show_preferences(&self) -> Bool {
+        println!("DEBUG: Showing preferences");
+
+        let mtm = self.mtm();
+        let window = self
+            .ivars()
+            .preferences_window
+            .get_or_init(|| self.create_preferences_window(mtm));
+        window.makeKeyAndOrderFront(None);
+
+        Bool::YES
+    }
+
+    // Report the source's focus metric in the status bar, along with where
+    // the sharpest part of it is
+    fn measure_sharpness(&self) -> Bool {
+        const REGION_CELLS: usize = 4;
+
+        let _ = self.ensure_pattern_cache();
+        let (sharpness, region) = match self.ivars().cached_pattern.borrow().as_ref() {
+            Some(cached) => (
+                cached.pattern.sharpness(),
+                cached.pattern.sharpest_region(REGION_CELLS),
+            ),
+            None => return Bool::NO,
+        };
+
+        match region {
+            Some((x, y, width, height)) => self.set_status(&format!(
+                "Sharpness {:.1} — sharpest near {}, {} ({}×{})",
+                sharpness, x, y, width, height
+            )),
+            None => self.set_status(&format!("Sharpness {:.1}", sharpness)),
+        }
+        Bool::YES
+    }
+
+    // Show the source's dominant colors as swatches; clicking a swatch's hex
+    // button copies it
+    fn show_dominant_colors(&self) -> Bool {
+        const SWATCH_COUNT: usize = 6;
+        const SWATCH_WIDTH: f64 = 90.;
+
+        let _ = self.ensure_pattern_cache();
+        let colors = match self.ivars().cached_pattern.borrow().as_ref() {
+            Some(cached) => cached.pattern.dominant_colors(SWATCH_COUNT),
+            None => return Bool::NO,
+        };
+        println!("DEBUG: Dominant colors: {:?}", colors);
+
+        let mtm = self.mtm();
+        let window = self
+            .ivars()
+            .dominant_colors_window
+            .get_or_init(|| self.create_dominant_colors_window(mtm));
+        let target: Option<&AnyObject> = Some(self.as_ref());
+
+        let content_size = NSSize::new(10. + colors.len() as f64 * SWATCH_WIDTH, 110.);
+        let content_view = unsafe {
+            NSView::initWithFrame(NSView::alloc(mtm), NSRect::new(NSPoint::ZERO, content_size))
+        };
+
+        for (index, color) in colors.iter().enumerate() {
+            let x = 10. + index as f64 * SWATCH_WIDTH;
+            let hex = NSString::from_str(&format!("#{}", &rgba_to_hex(*color)[..6]));
+
+            unsafe {
+                let swatch = NSTextField::labelWithString(ns_string!(""), mtm);
+                swatch.setFrame(NSRect::new(
+                    NSPoint::new(x, 45.),
+                    NSSize::new(SWATCH_WIDTH - 10., 55.),
+                ));
+                swatch.setDrawsBackground(true);
+                swatch.setBackgroundColor(Some(&rgba_to_ns_color(*color)));
+                content_view.addSubview(&swatch);
+
+                let button = NSButton::buttonWithTitle_target_action(
+                    &hex,
+                    target,
+                    Some(sel!(copySwatchColor:)),
+                    mtm,
+                );
+                button.setFrame(NSRect::new(
+                    NSPoint::new(x - 4., 10.),
+                    NSSize::new(SWATCH_WIDTH - 2., 30.),
+                ));
+                content_view.addSubview(&button);
+            }
+        }
+
+        window.setContentView(Some(&content_view));
+        window.setContentSize(content_size);
+        window.makeKeyAndOrderFront(None);
+
+        Bool::YES
+    }
+
+    fn create_dominant_colors_window(&self, mtm: MainThreadMarker) -> Retained<NSWindow> {
+        let window_frame = NSRect::new(NSPoint::new(200., 200.), NSSize::new(200., 110.));
+        let style = NSWindowStyleMask::Titled | NSWindowStyleMask::Closable;
+
+        let window = unsafe {
+            NSWindow::initWithContentRect_styleMask_backing_defer(
+                NSWindow::alloc(mtm),
+                window_frame,
+                style,
+                NSBackingStoreType::Buffered,
+                false,
+            )
+        };
+
+        unsafe { window.setReleasedWhenClosed(false) };
+        window.setTitle(ns_string!("Dominant Colors"));
+        window.center();
+
+        window
+    }
+
+    fn create_preferences_window(&self, mtm: MainThreadMarker) -> Retained<NSWindow> {
+        let window_frame = NSRect::new(NSPoint::new(200., 200.), NSSize::new(500., 240.));
+        let style = NSWindowStyleMask::Titled | NSWindowStyleMask::Closable;
+
+        let window = unsafe {
+            NSWindow::initWithContentRect_styleMask_backing_defer(
+                NSWindow::alloc(mtm),
+                window_frame,
+                style,
+                NSBackingStoreType::Buffered,
+                false,
+            )
+        };
+
+        unsafe { window.setReleasedWhenClosed(false) };
+        window.setTitle(ns_string!("Settings"));
+        window.center();
 
-                let response = panel.runModal();
+        let content_view = window.contentView().unwrap();
+        let tab_frame = NSRect::new(NSPoint::new(12., 12.), NSSize::new(476., 216.));
+        let tab_view = unsafe { NSTabView::initWithFrame(NSTabView::alloc(mtm), tab_frame) };
 
-                if response == 1 {
-                    let urls = panel.URLs();
-                    if let Some(url) = urls.firstObject() {
-                        println!("DEBUG: Selected file: {:?}", url);
-
-                        // Keep a reference to the URL
-                        *self.ivars().selected_file_path.borrow_mut() = Some(url.clone());
-
-                        // Extract filename from URL
-                        let filename = {
-                            println!("DEBUG: Raw URL: {:?}", url);
-
-                            let url_path = {
-                                if let Some(path) = url.path().as_deref() {
-                                    let ns_string = path.to_owned();
-                                    format!("{}", &*ns_string)
-                                } else {
-                                    "unknown_path".to_string()
-                                }
-                            };
-
-                            println!("DEBUG: Extracted path: {}", url_path);
-
-                            url_path.split('/').last()
-                                .unwrap_or("JP2 File")
-                                .to_string()
-                        };
-
-                        println!("DEBUG: Showing Coming Soon text pattern for JP2 file: {:?}", &filename);
-
-                        // Update state
-                        {
-                            let mut state = self.ivars().state.borrow_mut();
-                            state.pattern_type = PatternType::Text;
-                            state.primary_text = Some("COMING SOON".to_string());
-                            state.secondary_text = Some(filename.clone());
-                            state.file_name = Some(filename);
-
-                            // Reset view position and zoom
-                            state.view_x = 0.0;
-                            state.view_y = 0.0;
-                            state.zoom_level = 1.0;
-                        }
+        let state = self.ivars().state.borrow();
+        let target: Option<&AnyObject> = Some(self.as_ref());
 
-                        // Full render (will regenerate pattern since content changed)
-                        return self.render_ui();
-                    }
+        // Navigation: how mouse input moves the view
+        let navigation = self.add_preferences_tab(&tab_view, ns_string!("Navigation"), mtm);
+        unsafe {
+            let checkbox = NSButton::checkboxWithTitle_target_action(
+                ns_string!("Dragging moves the view instead of the image"),
+                target,
+                Some(sel!(panDirectionToggled:)),
+                mtm,
+            );
+            checkbox.setState(if state.pan_direction == PanDirection::Scroll {
+                NSControlStateValueOn
+            } else {
+                NSControlStateValueOff
+            });
+            checkbox.setFrameOrigin(NSPoint::new(20., 140.));
+            navigation.addSubview(&checkbox);
+
+            let checkbox = NSButton::checkboxWithTitle_target_action(
+                ns_string!("Remember zoom and position for each file"),
+                target,
+                Some(sel!(rememberFileViewStateToggled:)),
+                mtm,
+            );
+            checkbox.setState(if state.remember_file_view_state {
+                NSControlStateValueOn
+            } else {
+                NSControlStateValueOff
+            });
+            checkbox.setFrameOrigin(NSPoint::new(20., 116.));
+            navigation.addSubview(&checkbox);
+
+            let label = NSTextField::labelWithString(ns_string!("Pan speed:"), mtm);
+            label.setFrameOrigin(NSPoint::new(20., 84.));
+            navigation.addSubview(&label);
+
+            let slider_frame = NSRect::new(NSPoint::new(130., 80.), NSSize::new(200., 24.));
+            let slider = NSSlider::initWithFrame(NSSlider::alloc(mtm), slider_frame);
+            slider.setMinValue(0.25);
+            slider.setMaxValue(4.0);
+            slider.setDoubleValue(state.pan_sensitivity);
+            slider.setAction(Some(sel!(panSensitivityChanged:)));
+            slider.setTarget(target);
+            navigation.addSubview(&slider);
+
+            let label = NSTextField::labelWithString(ns_string!("Pinch zoom speed:"), mtm);
+            label.setFrameOrigin(NSPoint::new(20., 52.));
+            navigation.addSubview(&label);
+
+            let slider_frame = NSRect::new(NSPoint::new(130., 48.), NSSize::new(200., 24.));
+            let slider = NSSlider::initWithFrame(NSSlider::alloc(mtm), slider_frame);
+            slider.setMinValue(0.25);
+            slider.setMaxValue(4.0);
+            slider.setDoubleValue(state.zoom_gesture_sensitivity);
+            slider.setAction(Some(sel!(zoomGestureSensitivityChanged:)));
+            slider.setTarget(target);
+            navigation.addSubview(&slider);
+
+            let checkbox = NSButton::checkboxWithTitle_target_action(
+                ns_string!("Snap panning to whole pixels at integer zoom"),
+                target,
+                Some(sel!(snapPanToggled:)),
+                mtm,
+            );
+            checkbox.setState(if state.snap_pan_to_pixels {
+                NSControlStateValueOn
+            } else {
+                NSControlStateValueOff
+            });
+            checkbox.setFrameOrigin(NSPoint::new(20., 14.));
+            navigation.addSubview(&checkbox);
+        }
+
+        // Overlays: debug markers drawn on top of the pattern
+        let overlays = self.add_preferences_tab(&tab_view, ns_string!("Overlays"), mtm);
+        unsafe {
+            let checkbox = NSButton::checkboxWithTitle_target_action(
+                ns_string!("Show debug borders and corner markers"),
+                target,
+                Some(sel!(debugBordersToggled:)),
+                mtm,
+            );
+            checkbox.setState(if state.show_debug_borders {
+                NSControlStateValueOn
+            } else {
+                NSControlStateValueOff
+            });
+            checkbox.setFrameOrigin(NSPoint::new(20., 130.));
+            overlays.addSubview(&checkbox);
+
+            let label = NSTextField::labelWithString(ns_string!("Border inset:"), mtm);
+            label.setFrameOrigin(NSPoint::new(40., 104.));
+            overlays.addSubview(&label);
+
+            let slider_frame = NSRect::new(NSPoint::new(150., 100.), NSSize::new(180., 24.));
+            let slider = NSSlider::initWithFrame(NSSlider::alloc(mtm), slider_frame);
+            slider.setMinValue(0.0);
+            slider.setMaxValue(MAX_DEBUG_BORDER_INSET as f64);
+            slider.setNumberOfTickMarks(MAX_DEBUG_BORDER_INSET as isize + 1);
+            slider.setAllowsTickMarkValuesOnly(true);
+            slider.setDoubleValue(state.debug_border_inset as f64);
+            slider.setAction(Some(sel!(debugBorderInsetChanged:)));
+            slider.setTarget(target);
+            overlays.addSubview(&slider);
+
+            let checkbox = NSButton::checkboxWithTitle_target_action(
+                ns_string!("Blend corner markers with the pattern"),
+                target,
+                Some(sel!(blendDebugCornersToggled:)),
+                mtm,
+            );
+            checkbox.setState(if state.blend_debug_corners {
+                NSControlStateValueOn
+            } else {
+                NSControlStateValueOff
+            });
+            checkbox.setFrameOrigin(NSPoint::new(40., 72.));
+            overlays.addSubview(&checkbox);
+
+            let checkbox = NSButton::checkboxWithTitle_target_action(
+                ns_string!("Draw them over opened images too"),
+                target,
+                Some(sel!(debugBordersOnImagesToggled:)),
+                mtm,
+            );
+            checkbox.setState(if state.debug_borders_on_images {
+                NSControlStateValueOn
+            } else {
+                NSControlStateValueOff
+            });
+            checkbox.setFrameOrigin(NSPoint::new(40., 48.));
+            overlays.addSubview(&checkbox);
+
+            let checkbox = NSButton::checkboxWithTitle_target_action(
+                ns_string!("Hide controls in full screen when the mouse is idle"),
+                target,
+                Some(sel!(autoHideControlsToggled:)),
+                mtm,
+            );
+            checkbox.setState(if state.auto_hide_controls {
+                NSControlStateValueOn
+            } else {
+                NSControlStateValueOff
+            });
+            checkbox.setFrameOrigin(NSPoint::new(20., 16.));
+            overlays.addSubview(&checkbox);
+        }
+
+        // Colors: one color well per checkerboard square color, tagged with its index
+        let colors = self.add_preferences_tab(&tab_view, ns_string!("Colors"), mtm);
+        unsafe {
+            let label = NSTextField::labelWithString(ns_string!("Checkerboard:"), mtm);
+            label.setFrameOrigin(NSPoint::new(20., 136.));
+            colors.addSubview(&label);
+        }
+
+        for (index, rgba) in state.checker_colors.iter().enumerate() {
+            let well_frame = NSRect::new(
+                NSPoint::new(130. + index as f64 * 50., 130.),
+                NSSize::new(40., 30.),
+            );
+            let well = unsafe { NSColorWell::initWithFrame(NSColorWell::alloc(mtm), well_frame) };
+
+            unsafe {
+                well.setColor(&rgba_to_ns_color(*rgba));
+                well.setTag(index as isize);
+                well.setAction(Some(sel!(checkerColorChanged:)));
+                well.setTarget(target);
+
+                colors.addSubview(&well);
+            }
+        }
+
+        unsafe {
+            let checkbox = NSButton::checkboxWithTitle_target_action(
+                ns_string!("Smooth checkerboard edges at fractional zoom"),
+                target,
+                Some(sel!(smoothCheckerboardToggled:)),
+                mtm,
+            );
+            checkbox.setState(if state.smooth_checkerboard {
+                NSControlStateValueOn
+            } else {
+                NSControlStateValueOff
+            });
+            checkbox.setFrameOrigin(NSPoint::new(20., 96.));
+            colors.addSubview(&checkbox);
+
+            let label = NSTextField::labelWithString(ns_string!("Background:"), mtm);
+            label.setFrameOrigin(NSPoint::new(20., 60.));
+            colors.addSubview(&label);
+
+            let popup_frame = NSRect::new(NSPoint::new(128., 54.), NSSize::new(160., 26.));
+            let popup = NSPopUpButton::initWithFrame_pullsDown(
+                NSPopUpButton::alloc(mtm),
+                popup_frame,
+                false,
+            );
+            for background in ViewBackground::ALL {
+                popup.addItemWithTitle(&NSString::from_str(background.title()));
+            }
+            let selected = ViewBackground::ALL
+                .iter()
+                .position(|background| *background == state.view_background)
+                .unwrap_or(0);
+            popup.selectItemAtIndex(selected as isize);
+            popup.setAction(Some(sel!(viewBackgroundChanged:)));
+            popup.setTarget(target);
+            colors.addSubview(&popup);
+        }
+
+        // Text: sizing of the text pattern
+        let text = self.add_preferences_tab(&tab_view, ns_string!("Text"), mtm);
+        unsafe {
+            let label = NSTextField::labelWithString(ns_string!("Text size:"), mtm);
+            label.setFrameOrigin(NSPoint::new(20., 136.));
+            text.addSubview(&label);
+
+            let slider_frame = NSRect::new(NSPoint::new(130., 132.), NSSize::new(200., 24.));
+            let slider = NSSlider::initWithFrame(NSSlider::alloc(mtm), slider_frame);
+            slider.setMinValue(0.25);
+            slider.setMaxValue(4.0);
+            slider.setDoubleValue(state.text_scale);
+            slider.setAction(Some(sel!(textScaleChanged:)));
+            slider.setTarget(target);
+            text.addSubview(&slider);
+
+            let label = NSTextField::labelWithString(ns_string!("Direction:"), mtm);
+            label.setFrameOrigin(NSPoint::new(20., 100.));
+            text.addSubview(&label);
+
+            let popup_frame = NSRect::new(NSPoint::new(128., 94.), NSSize::new(160., 26.));
+            let popup = NSPopUpButton::initWithFrame_pullsDown(
+                NSPopUpButton::alloc(mtm),
+                popup_frame,
+                false,
+            );
+            for direction in TextDirection::ALL {
+                popup.addItemWithTitle(&NSString::from_str(direction.title()));
+            }
+            let selected = TextDirection::ALL
+                .iter()
+                .position(|direction| *direction == state.text_direction)
+                .unwrap_or(0);
+            popup.selectItemAtIndex(selected as isize);
+            popup.setAction(Some(sel!(textDirectionChanged:)));
+            popup.setTarget(target);
+            text.addSubview(&popup);
+        }
+
+        // Filters: per-pixel filters applied to the viewport. These are inspection
+        // tools for the current session, so they aren't persisted.
+        let filters = self.add_preferences_tab(&tab_view, ns_string!("Filters"), mtm);
+        unsafe {
+            let checkbox = NSButton::checkboxWithTitle_target_action(
+                ns_string!("Chroma key"),
+                target,
+                Some(sel!(chromaKeyToggled:)),
+                mtm,
+            );
+            checkbox.setState(if state.chroma_key_enabled {
+                NSControlStateValueOn
+            } else {
+                NSControlStateValueOff
+            });
+            checkbox.setFrameOrigin(NSPoint::new(20., 136.));
+            filters.addSubview(&checkbox);
+
+            let [r, g, b] = state.chroma_key_color;
+            let well_frame = NSRect::new(NSPoint::new(130., 130.), NSSize::new(40., 30.));
+            let well = NSColorWell::initWithFrame(NSColorWell::alloc(mtm), well_frame);
+            well.setColor(&rgba_to_ns_color([r, g, b, 255]));
+            well.setAction(Some(sel!(chromaKeyColorChanged:)));
+            well.setTarget(target);
+            filters.addSubview(&well);
+
+            let label = NSTextField::labelWithString(ns_string!("Tolerance:"), mtm);
+            label.setFrameOrigin(NSPoint::new(20., 100.));
+            filters.addSubview(&label);
+
+            let slider_frame = NSRect::new(NSPoint::new(130., 96.), NSSize::new(200., 24.));
+            let slider = NSSlider::initWithFrame(NSSlider::alloc(mtm), slider_frame);
+            slider.setMinValue(0.0);
+            slider.setMaxValue(255.0);
+            slider.setDoubleValue(state.chroma_key_tolerance as f64);
+            slider.setAction(Some(sel!(chromaKeyToleranceChanged:)));
+            slider.setTarget(target);
+            filters.addSubview(&slider);
+
+            let label = NSTextField::labelWithString(ns_string!("Simulate:"), mtm);
+            label.setFrameOrigin(NSPoint::new(20., 60.));
+            filters.addSubview(&label);
+
+            let popup_frame = NSRect::new(NSPoint::new(128., 54.), NSSize::new(240., 26.));
+            let popup = NSPopUpButton::initWithFrame_pullsDown(
+                NSPopUpButton::alloc(mtm),
+                popup_frame,
+                false,
+            );
+            popup.addItemWithTitle(ns_string!("Off"));
+            for kind in ColorBlindType::ALL {
+                popup.addItemWithTitle(&NSString::from_str(kind.title()));
+            }
+            let selected = state
+                .color_blind_simulation
+                .and_then(|selected| {
+                    ColorBlindType::ALL
+                        .iter()
+                        .position(|kind| *kind == selected)
+                })
+                .map_or(0, |index| index + 1);
+            popup.selectItemAtIndex(selected as isize);
+            popup.setAction(Some(sel!(colorBlindSimulationChanged:)));
+            popup.setTarget(target);
+            filters.addSubview(&popup);
+        }
+
+        // Tools: which modifier key triggers each click tool
+        let tools = self.add_preferences_tab(&tab_view, ns_string!("Tools"), mtm);
+        unsafe {
+            for (index, tool) in ModifierTool::ALL.into_iter().enumerate() {
+                let y = 130. - 34. * index as f64;
+
+                let label = NSTextField::labelWithString(&NSString::from_str(tool.title()), mtm);
+                label.setFrameOrigin(NSPoint::new(20., y + 6.));
+                tools.addSubview(&label);
+
+                let popup_frame = NSRect::new(NSPoint::new(148., y), NSSize::new(180., 26.));
+                let popup = NSPopUpButton::initWithFrame_pullsDown(
+                    NSPopUpButton::alloc(mtm),
+                    popup_frame,
+                    false,
+                );
+                for modifier in ToolModifier::ALL {
+                    popup.addItemWithTitle(&NSString::from_str(modifier.title()));
                 }
+                let selected = ToolModifier::ALL
+                    .iter()
+                    .position(|modifier| *modifier == state.tool_modifiers[index])
+                    .unwrap_or(0);
+                popup.selectItemAtIndex(selected as isize);
+                popup.setTag(index as isize);
+                popup.setAction(Some(sel!(toolModifierChanged:)));
+                popup.setTarget(target);
+                tools.addSubview(&popup);
             }
+        }
 
-            Bool::NO
+        unsafe { content_view.addSubview(&tab_view) };
+
+        window
+    }
+
+    // Add a tab to the preferences tab view and return the view to place controls in
+    fn add_preferences_tab(
+        &self,
+        tab_view: &NSTabView,
+        label: &NSString,
+        mtm: MainThreadMarker,
+    ) -> Retained<NSView> {
+        unsafe {
+            let item = NSTabViewItem::new();
+            item.setLabel(label);
+
+            let view = NSView::initWithFrame(NSView::alloc(mtm), tab_view.contentRect());
+            item.setView(Some(&view));
+            tab_view.addTabViewItem(&item);
+
+            view
         }
+    }
 
-        #[unsafe(method(createGradient:))]
-        fn createGradient(&self, _sender: Option<&NSObject>) -> Bool {
-            println!("DEBUG: Creating gradient image");
+    // Apply preferences saved in NSUserDefaults to the current state
+    fn load_preferences(&self) {
+        let defaults = unsafe { NSUserDefaults::standardUserDefaults() };
+        let mut state = self.ivars().state.borrow_mut();
 
-            // Update state
-            self.ivars().state.borrow_mut().pattern_type = PatternType::Gradient;
+        unsafe {
+            for (index, key) in PREF_CHECKER_COLORS.iter().enumerate() {
+                let hex = defaults.stringForKey(&NSString::from_str(key));
+                if let Some(rgba) = hex.and_then(|hex| hex_to_rgba(&hex.to_string())) {
+                    state.checker_colors[index] = rgba;
+                }
+            }
 
-            // Full render (will regenerate pattern since type changed)
-            self.render_ui()
+            let key = NSString::from_str(PREF_SHOW_DEBUG_BORDERS);
+            if defaults.objectForKey(&key).is_some() {
+                state.show_debug_borders = defaults.boolForKey(&key);
+            }
+
+            state.smooth_checkerboard =
+                defaults.boolForKey(&NSString::from_str(PREF_SMOOTH_CHECKERBOARD));
+
+            let key = NSString::from_str(PREF_DEBUG_BORDER_INSET);
+            if defaults.objectForKey(&key).is_some() {
+                state.debug_border_inset =
+                    (defaults.integerForKey(&key).max(0) as usize).min(MAX_DEBUG_BORDER_INSET);
+            }
+
+            state.blend_debug_corners =
+                defaults.boolForKey(&NSString::from_str(PREF_BLEND_DEBUG_CORNERS));
+            state.debug_borders_on_images =
+                defaults.boolForKey(&NSString::from_str(PREF_DEBUG_BORDERS_ON_IMAGES));
+
+            let key = NSString::from_str(PREF_TEXT_SCALE);
+            if defaults.objectForKey(&key).is_some() {
+                state.text_scale = defaults.doubleForKey(&key).clamp(0.25, 4.0);
+            }
+
+            let index = defaults.integerForKey(&NSString::from_str(PREF_TEXT_DIRECTION));
+            if let Some(direction) = usize::try_from(index)
+                .ok()
+                .and_then(|index| TextDirection::ALL.get(index))
+            {
+                state.text_direction = *direction;
+            }
+
+            for (index, key) in PREF_TOOL_MODIFIERS.iter().enumerate() {
+                let key = NSString::from_str(key);
+                if defaults.objectForKey(&key).is_none() {
+                    continue;
+                }
+                if let Some(modifier) = usize::try_from(defaults.integerForKey(&key))
+                    .ok()
+                    .and_then(|index| ToolModifier::ALL.get(index))
+                {
+                    state.tool_modifiers[index] = *modifier;
+                }
+            }
+
+            let index = defaults.integerForKey(&NSString::from_str(PREF_VIEW_BACKGROUND));
+            if let Some(background) = usize::try_from(index)
+                .ok()
+                .and_then(|index| ViewBackground::ALL.get(index))
+            {
+                state.view_background = *background;
+            }
+
+            let key = NSString::from_str(PREF_AUTO_HIDE_CONTROLS);
+            if defaults.objectForKey(&key).is_some() {
+                state.auto_hide_controls = defaults.boolForKey(&key);
+            }
+
+            if defaults.boolForKey(&NSString::from_str(PREF_SCROLL_STYLE_PANNING)) {
+                state.pan_direction = PanDirection::Scroll;
+            }
+
+            let key = NSString::from_str(PREF_PAN_SENSITIVITY);
+            if defaults.objectForKey(&key).is_some() {
+                state.pan_sensitivity = defaults.doubleForKey(&key).clamp(0.25, 4.0);
+            }
+
+            let key = NSString::from_str(PREF_ZOOM_GESTURE_SENSITIVITY);
+            if defaults.objectForKey(&key).is_some() {
+                state.zoom_gesture_sensitivity = defaults.doubleForKey(&key).clamp(0.25, 4.0);
+            }
+
+            state.snap_pan_to_pixels =
+                defaults.boolForKey(&NSString::from_str(PREF_SNAP_PAN_TO_PIXELS));
+
+            let key = NSString::from_str(PREF_REMEMBER_FILE_VIEW_STATE);
+            if defaults.objectForKey(&key).is_some() {
+                state.remember_file_view_state = defaults.boolForKey(&key);
+            }
         }
+    }
 
-        #[unsafe(method(createCheckerboard:))]
-        fn createCheckerboard(&self, _sender: Option<&NSObject>) -> Bool {
-            println!("DEBUG: Creating checkerboard image");
+    // Persist the current preference values to NSUserDefaults
+    fn save_preferences(&self) {
+        let defaults = unsafe { NSUserDefaults::standardUserDefaults() };
+        let state = self.ivars().state.borrow();
 
-            // Update state
-            self.ivars().state.borrow_mut().pattern_type = PatternType::Checkerboard;
+        unsafe {
+            for (index, key) in PREF_CHECKER_COLORS.iter().enumerate() {
+                let hex = NSString::from_str(&rgba_to_hex(state.checker_colors[index]));
+                let value: &AnyObject = &hex;
+                defaults.setObject_forKey(Some(value), &NSString::from_str(key));
+            }
 
-            // Full render (will regenerate pattern since type changed)
-            self.render_ui()
+            defaults.setBool_forKey(
+                state.show_debug_borders,
+                &NSString::from_str(PREF_SHOW_DEBUG_BORDERS),
+            );
+            defaults.setBool_forKey(
+                state.smooth_checkerboard,
+                &NSString::from_str(PREF_SMOOTH_CHECKERBOARD),
+            );
+            defaults.setInteger_forKey(
+                state.debug_border_inset as isize,
+                &NSString::from_str(PREF_DEBUG_BORDER_INSET),
+            );
+            defaults.setBool_forKey(
+                state.blend_debug_corners,
+                &NSString::from_str(PREF_BLEND_DEBUG_CORNERS),
+            );
+            defaults.setBool_forKey(
+                state.debug_borders_on_images,
+                &NSString::from_str(PREF_DEBUG_BORDERS_ON_IMAGES),
+            );
+            defaults.setDouble_forKey(state.text_scale, &NSString::from_str(PREF_TEXT_SCALE));
+            let direction_index = TextDirection::ALL
+                .iter()
+                .position(|direction| *direction == state.text_direction)
+                .unwrap_or(0);
+            defaults.setInteger_forKey(
+                direction_index as isize,
+                &NSString::from_str(PREF_TEXT_DIRECTION),
+            );
+            let background_index = ViewBackground::ALL
+                .iter()
+                .position(|background| *background == state.view_background)
+                .unwrap_or(0);
+            defaults.setInteger_forKey(
+                background_index as isize,
+                &NSString::from_str(PREF_VIEW_BACKGROUND),
+            );
+            for (index, key) in PREF_TOOL_MODIFIERS.iter().enumerate() {
+                let modifier_index = ToolModifier::ALL
+                    .iter()
+                    .position(|modifier| *modifier == state.tool_modifiers[index])
+                    .unwrap_or(0);
+                defaults.setInteger_forKey(modifier_index as isize, &NSString::from_str(key));
+            }
+            defaults.setBool_forKey(
+                state.auto_hide_controls,
+                &NSString::from_str(PREF_AUTO_HIDE_CONTROLS),
+            );
+            defaults.setBool_forKey(
+                state.pan_direction == PanDirection::Scroll,
+                &NSString::from_str(PREF_SCROLL_STYLE_PANNING),
+            );
+            defaults.setDouble_forKey(
+                state.pan_sensitivity,
+                &NSString::from_str(PREF_PAN_SENSITIVITY),
+            );
+            defaults.setDouble_forKey(
+                state.zoom_gesture_sensitivity,
+                &NSString::from_str(PREF_ZOOM_GESTURE_SENSITIVITY),
+            );
+            defaults.setBool_forKey(
+                state.snap_pan_to_pixels,
+                &NSString::from_str(PREF_SNAP_PAN_TO_PIXELS),
+            );
+            defaults.setBool_forKey(
+                state.remember_file_view_state,
+                &NSString::from_str(PREF_REMEMBER_FILE_VIEW_STATE),
+            );
         }
+    }
 
-        #[unsafe(method(zoomChanged:))]
-        fn zoomChanged(&self, sender: Option<&NSObject>) -> Bool {
-            if let Some(obj) = sender {
-                let slider_value: f64 = unsafe { msg_send![obj, doubleValue] };
-                println!("DEBUG: Zoom changed to {}", slider_value);
+    // The remembered view state for a file, if remembering is enabled
+    fn saved_view_state(&self, path: &str) -> Option<ViewState> {
+        if !self.ivars().state.borrow().remember_file_view_state {
+            return None;
+        }
+        let defaults = unsafe { NSUserDefaults::standardUserDefaults() };
+        saved_view_state(&defaults, path)
+    }
+
+    // Store the open file's current zoom and pan, if remembering is enabled
+    fn remember_view_state(&self) {
+        let view_state = {
+            let state = self.ivars().state.borrow();
+            if !state.remember_file_view_state {
+                return;
+            }
+            ViewState {
+                zoom_level: state.zoom_level,
+                view_x: state.view_x,
+                view_y: state.view_y,
+            }
+        };
+        let path = self
+            .ivars()
+            .selected_file_path
+            .borrow()
+            .as_deref()
+            .and_then(|url| unsafe { url.path() });
+        let Some(path) = path else {
+            return;
+        };
 
-                // Update state
-                self.ivars().state.borrow_mut().zoom_level = slider_value.max(0.1).min(10.0);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0.0, |elapsed| elapsed.as_secs_f64());
+        let defaults = unsafe { NSUserDefaults::standardUserDefaults() };
+        store_view_state(&defaults, &path, view_state, now);
+    }
 
-                // Only render the viewport (not regenerate pattern)
-                self.render_viewport()
+    // Open and display a file, chosen in the open panel or dropped on the view
+    fn open_url(&self, url: &NSURL) -> Bool {
+        println!("DEBUG: Selected file: {:?}", url);
+
+        // Extract path and filename from URL
+        println!("DEBUG: Raw URL: {:?}", url);
+
+        let url_path = {
+            if let Some(path) = unsafe { url.path() }.as_deref() {
+                let ns_string = path.to_owned();
+                format!("{}", &*ns_string)
             } else {
-                Bool::NO
+                "unknown_path".to_string()
+            }
+        };
+
+        println!("DEBUG: Extracted path: {}", url_path);
+
+        let filename = url_path.split('/').last().unwrap_or("JP2 File").to_string();
+
+        // Read the file and sniff its format before deciding what to show.
+        // Recognized formats without a decoder are refused like unknown ones.
+        let load_start = Instant::now();
+        let (decoded, primary_text) = match load_image(Path::new(&url_path)) {
+            Ok(loaded) => {
+                let decoded = match loaded.format {
+                    ImageFormat::Jp2 => Some(SourcePattern::from_jp2_bytes(&loaded.bytes)),
+                    _ => None,
+                };
+                let elapsed_ms = load_start.elapsed().as_millis();
+                println!(
+                    "DEBUG: Detected {:?} image ({} bytes) for: {:?}",
+                    loaded.format,
+                    loaded.bytes.len(),
+                    &filename
+                );
+                let loaded_status = format!(
+                    "Loaded {} — {} — {}ms",
+                    filename,
+                    loaded.describe(),
+                    elapsed_ms
+                );
+
+                match decoded {
+                    Some(Ok(pattern)) => {
+                        // The focus metric works on a downsampled copy, so
+                        // it's cheap enough to report for every load
+                        self.set_status(&format!(
+                            "{} — sharpness {:.1}",
+                            loaded_status,
+                            pattern.sharpness()
+                        ));
+                        (Some(pattern), "")
+                    }
+                    Some(Err(err)) => {
+                        self.set_status(&format!("Failed to load {}: {}", filename, err));
+                        (None, "FILE ERROR")
+                    }
+                    None => {
+                        // Keep showing whatever was on screen before
+                        let name = loaded.format.name();
+                        self.set_status(&format!(
+                            "Can't open {}: {} isn't supported",
+                            filename, name
+                        ));
+                        self.show_unsupported_format_alert(&filename, &format!("a {} image", name));
+                        return Bool::NO;
+                    }
+                }
+            }
+            Err(DecodeError::UnsupportedFormat(guess)) => {
+                // Keep showing whatever was on screen before
+                self.set_status(&format!("Can't open {}: unsupported format", filename));
+                self.show_unsupported_format_alert(&filename, &guess);
+                return Bool::NO;
             }
+            Err(err) => {
+                self.set_status(&format!("Failed to load {}: {}", filename, err));
+                (None, "FILE ERROR")
+            }
+        };
+
+        // Remember where the previous file was left before switching
+        self.remember_view_state();
+        let saved_view_state = self.saved_view_state(&url_path);
+
+        // Keep a reference to the URL
+        *self.ivars().selected_file_path.borrow_mut() = Some(url.retain());
+
+        // Update state
+        {
+            let mut state = self.ivars().state.borrow_mut();
+            match decoded {
+                Some(pattern) => {
+                    state.source_width = pattern.width;
+                    state.source_height = pattern.height;
+                    state.pattern_type = PatternType::Image {
+                        rgba: pattern.buffer.into(),
+                        width: pattern.width,
+                        height: pattern.height,
+                    };
+                }
+                None => {
+                    state.pattern_type = PatternType::Text;
+                    state.primary_text = Some(primary_text.to_string());
+                    state.source_width = 800;
+                    state.source_height = 600;
+                }
+            }
+            state.secondary_text = Some(filename.clone());
+            state.file_name = Some(filename);
+
+            // Restore the file's last view, or reset it
+            let view_state = saved_view_state.unwrap_or(ViewState {
+                zoom_level: 1.0,
+                view_x: 0.0,
+                view_y: 0.0,
+            });
+            state.zoom_level = view_state.zoom_level;
+            state.pinned_region = None;
+            state.set_pan(view_state.view_x, view_state.view_y);
+        }
+
+        // Full render (will regenerate pattern since content changed)
+        let rendered = self.render_ui();
+
+        // Files seen for the first time start out fitted to the window
+        if saved_view_state.is_none() {
+            return self.zoom_to_fit();
+        }
+        rendered
+    }
+
+    // Build the menu bar: the application menu and a File menu. Items without
+    // a target go to the first responder chain, which ends at NSApp.
+    fn setup_main_menu(&self, mtm: MainThreadMarker) {
+        let main_menu = unsafe { NSMenu::initWithTitle(NSMenu::alloc(mtm), ns_string!("")) };
+        let target: Option<&AnyObject> = Some(self.as_ref());
+
+        unsafe {
+            let app_menu = NSMenu::initWithTitle(NSMenu::alloc(mtm), ns_string!("JP2 Viewer"));
+
+            let about_item = NSMenuItem::initWithTitle_action_keyEquivalent(
+                NSMenuItem::alloc(mtm),
+                ns_string!("About JP2 Viewer"),
+                Some(sel!(orderFrontStandardAboutPanel:)),
+                ns_string!(""),
+            );
+            app_menu.addItem(&about_item);
+
+            app_menu.addItem(&NSMenuItem::separatorItem(mtm));
+
+            let preferences_item = NSMenuItem::initWithTitle_action_keyEquivalent(
+                NSMenuItem::alloc(mtm),
+                ns_string!("Settings…"),
+                Some(sel!(showPreferences:)),
+                ns_string!(","),
+            );
+            preferences_item.setTarget(target);
+            app_menu.addItem(&preferences_item);
+
+            app_menu.addItem(&NSMenuItem::separatorItem(mtm));
+
+            let quit_item = NSMenuItem::initWithTitle_action_keyEquivalent(
+                NSMenuItem::alloc(mtm),
+                ns_string!("Quit JP2 Viewer"),
+                Some(sel!(terminate:)),
+                ns_string!("q"),
+            );
+            app_menu.addItem(&quit_item);
+
+            let app_menu_item = NSMenuItem::new(mtm);
+            app_menu_item.setSubmenu(Some(&app_menu));
+            main_menu.addItem(&app_menu_item);
+
+            let file_menu = NSMenu::initWithTitle(NSMenu::alloc(mtm), ns_string!("File"));
+
+            let open_item = NSMenuItem::initWithTitle_action_keyEquivalent(
+                NSMenuItem::alloc(mtm),
+                ns_string!("Open…"),
+                Some(sel!(openFile:)),
+                ns_string!("o"),
+            );
+            open_item.setTarget(target);
+            file_menu.addItem(&open_item);
+
+            let save_item = NSMenuItem::initWithTitle_action_keyEquivalent(
+                NSMenuItem::alloc(mtm),
+                ns_string!("Save Image…"),
+                Some(sel!(saveImage:)),
+                ns_string!("s"),
+            );
+            save_item.setTarget(target);
+            file_menu.addItem(&save_item);
+
+            file_menu.addItem(&NSMenuItem::separatorItem(mtm));
+
+            // ⇧⌘R, disabled until a file is open
+            let reveal_item = NSMenuItem::initWithTitle_action_keyEquivalent(
+                NSMenuItem::alloc(mtm),
+                ns_string!("Reveal in Finder"),
+                Some(sel!(revealInFinder:)),
+                ns_string!("R"),
+            );
+            reveal_item.setTarget(target);
+            file_menu.addItem(&reveal_item);
+
+            let file_menu_item = NSMenuItem::new(mtm);
+            file_menu_item.setSubmenu(Some(&file_menu));
+            main_menu.addItem(&file_menu_item);
+
+            // Standard editing items have no target, so they go to the first
+            // responder (e.g. a text field in Settings)
+            let edit_menu = NSMenu::initWithTitle(NSMenu::alloc(mtm), ns_string!("Edit"));
+            for (title, action, key) in [
+                (ns_string!("Cut"), sel!(cut:), ns_string!("x")),
+                (ns_string!("Copy"), sel!(copy:), ns_string!("c")),
+                (ns_string!("Paste"), sel!(paste:), ns_string!("v")),
+                (ns_string!("Select All"), sel!(selectAll:), ns_string!("a")),
+            ] {
+                let item = NSMenuItem::initWithTitle_action_keyEquivalent(
+                    NSMenuItem::alloc(mtm),
+                    title,
+                    Some(action),
+                    key,
+                );
+                edit_menu.addItem(&item);
+            }
+
+            edit_menu.addItem(&NSMenuItem::separatorItem(mtm));
+
+            // ⌥⌘C, as in the Finder
+            let copy_path_item = NSMenuItem::initWithTitle_action_keyEquivalent(
+                NSMenuItem::alloc(mtm),
+                ns_string!("Copy Path"),
+                Some(sel!(copyImagePath:)),
+                ns_string!("c"),
+            );
+            copy_path_item.setKeyEquivalentModifierMask(
+                NSEventModifierFlags::Command | NSEventModifierFlags::Option,
+            );
+            copy_path_item.setTarget(target);
+            edit_menu.addItem(&copy_path_item);
+
+            let edit_menu_item = NSMenuItem::new(mtm);
+            edit_menu_item.setSubmenu(Some(&edit_menu));
+            main_menu.addItem(&edit_menu_item);
+
+            // The same toggles as the single-key shortcuts, checked while on
+            let view_menu = NSMenu::initWithTitle(NSMenu::alloc(mtm), ns_string!("View"));
+            for entry in [
+                Some((ns_string!("Actual Size"), sel!(actualSize:))),
+                Some((ns_string!("Zoom to Fit"), sel!(zoomToFit:))),
+                None,
+                Some((ns_string!("Rulers"), sel!(toggleRulers:))),
+                Some((ns_string!("Debug Borders Only"), sel!(toggleBordersOnly:))),
+                Some((ns_string!("Pin Visible Region"), sel!(togglePinnedRegion:))),
+                Some((ns_string!("Freeze View"), sel!(toggleFreeze:))),
+                None,
+                Some((ns_string!("Keyboard Shortcuts"), sel!(toggleHelpOverlay:))),
+            ] {
+                let Some((title, action)) = entry else {
+                    view_menu.addItem(&NSMenuItem::separatorItem(mtm));
+                    continue;
+                };
+                let item = NSMenuItem::initWithTitle_action_keyEquivalent(
+                    NSMenuItem::alloc(mtm),
+                    title,
+                    Some(action),
+                    ns_string!(""),
+                );
+                item.setTarget(target);
+                view_menu.addItem(&item);
+            }
+
+            let view_menu_item = NSMenuItem::new(mtm);
+            view_menu_item.setSubmenu(Some(&view_menu));
+            main_menu.addItem(&view_menu_item);
+        }
+
+        NSApplication::sharedApplication(mtm).setMainMenu(Some(&main_menu));
+    }
+
+    fn create_context_menu(&self, mtm: MainThreadMarker) -> Retained<NSMenu> {
+        let menu = unsafe { NSMenu::initWithTitle(NSMenu::alloc(mtm), ns_string!("Image")) };
+        let target: Option<&AnyObject> = Some(self.as_ref());
+
+        unsafe {
+            let reveal_item = NSMenuItem::initWithTitle_action_keyEquivalent(
+                NSMenuItem::alloc(mtm),
+                ns_string!("Reveal in Finder"),
+                Some(sel!(revealInFinder:)),
+                ns_string!(""),
+            );
+            reveal_item.setTarget(target);
+            menu.addItem(&reveal_item);
+
+            let copy_path_item = NSMenuItem::initWithTitle_action_keyEquivalent(
+                NSMenuItem::alloc(mtm),
+                ns_string!("Copy Path"),
+                Some(sel!(copyImagePath:)),
+                ns_string!(""),
+            );
+            copy_path_item.setTarget(target);
+            menu.addItem(&copy_path_item);
+
+            let copy_pdf_item = NSMenuItem::initWithTitle_action_keyEquivalent(
+                NSMenuItem::alloc(mtm),
+                ns_string!("Copy as PDF"),
+                Some(sel!(copyViewAsPDF:)),
+                ns_string!(""),
+            );
+            copy_pdf_item.setTarget(target);
+            menu.addItem(&copy_pdf_item);
+
+            menu.addItem(&NSMenuItem::separatorItem(mtm));
+
+            let colors_item = NSMenuItem::initWithTitle_action_keyEquivalent(
+                NSMenuItem::alloc(mtm),
+                ns_string!("Dominant Colors"),
+                Some(sel!(showDominantColors:)),
+                ns_string!(""),
+            );
+            colors_item.setTarget(target);
+            menu.addItem(&colors_item);
+
+            let sharpness_item = NSMenuItem::initWithTitle_action_keyEquivalent(
+                NSMenuItem::alloc(mtm),
+                ns_string!("Measure Sharpness"),
+                Some(sel!(measureSharpness:)),
+                ns_string!(""),
+            );
+            sharpness_item.setTarget(target);
+            menu.addItem(&sharpness_item);
+
+            menu.addItem(&NSMenuItem::separatorItem(mtm));
+
+            let stats_item = NSMenuItem::initWithTitle_action_keyEquivalent(
+                NSMenuItem::alloc(mtm),
+                ns_string!("Export Render Stats"),
+                Some(sel!(exportRenderStats:)),
+                ns_string!(""),
+            );
+            stats_item.setTarget(target);
+            menu.addItem(&stats_item);
+        }
+
+        menu
+    }
+
+    // Explain that a file couldn't be opened because of its format, naming
+    // what it appears to be and the formats that are supported
+    fn show_unsupported_format_alert(&self, filename: &str, guess: &str) {
+        let supported = ImageFormat::ALL
+            .iter()
+            .filter(|format| format.decodes())
+            .map(|format| format.name())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let described = if guess == "unknown" {
+            "It isn't in a recognized image format".to_string()
+        } else {
+            format!("It appears to be {}, which JP2 Viewer can't display", guess)
+        };
+
+        unsafe {
+            let alert = NSAlert::new(self.mtm());
+            alert.setMessageText(&NSString::from_str(&format!("Can't open \"{}\"", filename)));
+            alert.setInformativeText(&NSString::from_str(&format!(
+                "{}. Supported formats: {}.",
+                described, supported
+            )));
+            alert.runModal();
         }
+    }
+
+    // Forget the opened file once a generated pattern replaces it on screen
+    fn clear_current_file(&self) {
+        self.remember_view_state();
+        *self.ivars().selected_file_path.borrow_mut() = None;
+        self.ivars().state.borrow_mut().file_name = None;
+    }
 
-        #[unsafe(method(mouseDown:))]
-        fn mouseDown(&self, event: &NSEvent) -> Bool {
-            println!("DEBUG: Mouse down received");
-            *self.ivars().is_panning.borrow_mut() = true;
+    // Convert a point in window coordinates to source pattern coordinates
+    fn window_to_source(&self, location: NSPoint) -> Option<(f64, f64)> {
+        let (point_x, point_y) = self.window_to_image_point(location)?;
 
-            let location = unsafe { event.locationInWindow() };
-            *self.ivars().last_mouse_location.borrow_mut() = location;
+        let state = self.ivars().state.borrow();
+        let (width, height) = state.viewport_size;
+        let (left, top, _, _) = state.visible_source_rect(width, height);
+        let source_x = left + point_x / state.zoom_level;
+        let source_y = top + point_y / state.zoom_level;
 
-            Bool::YES
+        Some((source_x, source_y))
+    }
+
+    // Convert a point in window coordinates to the image view, in image
+    // pixels measured from its top-left corner
+    fn window_to_image_point(&self, location: NSPoint) -> Option<(f64, f64)> {
+        let image_view = self.ivars().image_view.get()?;
+        let point = image_view.convertPoint_fromView(location, None);
+
+        // The image view isn't flipped, so rows are counted down from its top edge
+        let scale = self.backing_scale();
+        Some((
+            point.x * scale,
+            (image_view.frame().size.height - point.y) * scale,
+        ))
+    }
+
+    // Center of the visible part of the image, in image pixels measured from
+    // its top-left corner
+    fn visible_center_point(&self) -> Option<(f64, f64)> {
+        let image_view = self.ivars().image_view.get()?;
+        let visible = image_view.visibleRect();
+        if visible.size.width <= 0.0 || visible.size.height <= 0.0 {
+            return None;
         }
 
-        #[unsafe(method(mouseDragged:))]
-        fn mouseDragged(&self, event: &NSEvent) -> Bool {
-            println!("DEBUG: Mouse dragged");
-            if *self.ivars().is_panning.borrow() {
-                let current_location = unsafe { event.locationInWindow() };
-                let last_location = *self.ivars().last_mouse_location.borrow();
+        // The image view isn't flipped, so rows are counted down from its top edge
+        let height = image_view.frame().size.height;
+        let scale = self.backing_scale();
+        Some((
+            (visible.origin.x + visible.size.width / 2.0) * scale,
+            (height - (visible.origin.y + visible.size.height / 2.0)) * scale,
+        ))
+    }
 
-                let delta_x = current_location.x - last_location.x;
-                let delta_y = current_location.y - last_location.y;
+    // Backing pixels per point for the window, 2.0 on Retina displays
+    fn backing_scale(&self) -> f64 {
+        self.ivars()
+            .window
+            .get()
+            .map(|window| window.backingScaleFactor())
+            .filter(|scale| *scale > 0.0)
+            .unwrap_or(1.0)
+    }
 
-                // Update state
-                {
-                    let mut state = self.ivars().state.borrow_mut();
-                    state.view_x -= delta_x;
-                    state.view_y -= delta_y;
-                }
+    // Size of the scroll view's visible area in image pixels, the units of
+    // the view offset
+    fn viewport_size(&self) -> Option<(f64, f64)> {
+        let scroll_view = self.ivars().scroll_view.get()?;
+        let size = unsafe { scroll_view.contentSize() };
+        let scale = self.backing_scale();
+        Some((size.width * scale, size.height * scale))
+    }
 
-                // Only render the viewport (not regenerate pattern)
-                let _ = self.render_viewport();
+    fn setup_status_label(&self, window: &NSWindow, mtm: MainThreadMarker) {
+        let content_view = window.contentView().unwrap();
+        let content_frame = content_view.bounds();
 
-                *self.ivars().last_mouse_location.borrow_mut() = current_location;
-                return Bool::YES;
-            }
+        // Single-line label along the bottom edge, below the controls
+        let label_frame = NSRect::new(
+            NSPoint::new(20., 2.),
+            NSSize::new((content_frame.size.width - 40.).max(0.0), 16.),
+        );
 
-            Bool::NO
-        }
+        unsafe {
+            let label = NSTextField::labelWithString(ns_string!(""), mtm);
+            label.setFrame(label_frame);
+            label.setAutoresizingMask(NSAutoresizingMaskOptions::ViewWidthSizable);
 
-        #[unsafe(method(mouseUp:))]
-        fn mouseUp(&self, _event: &NSEvent) -> Bool {
-            println!("DEBUG: Mouse up received");
-            *self.ivars().is_panning.borrow_mut() = false;
-            Bool::YES
+            content_view.addSubview(&label);
+
+            let _ = self.ivars().status_label.set(label);
         }
+    }
 
-        #[unsafe(method(handlePinchGesture:))]
-        fn handlePinchGesture(&self, sender: Option<&NSObject>) -> Bool {
-            if let Some(recognizer) = sender {
-                unsafe {
-                    let state: isize = msg_send![recognizer, state];
+    // Show a short message in the status label (and log it)
+    fn set_status(&self, message: &str) {
+        println!("STATUS: {}", message);
 
-                    // Handle different gesture states
-                    if state == 1 { // GSBegan (1)
-                        println!("DEBUG: Pinch gesture began");
+        if let Some(label) = self.ivars().status_label.get() {
+            unsafe { label.setStringValue(&NSString::from_str(message)) };
+        }
+    }
 
-                        // Store current zoom level as base for this gesture sequence
-                        *self.ivars().base_zoom_level.borrow_mut() = self.ivars().state.borrow().zoom_level;
-                    }
+    fn setup_mouse_handling(&self, _window: &NSWindow) {
+        // Initial values
+        *self.ivars().is_panning.borrow_mut() = false;
+        *self.ivars().last_mouse_location.borrow_mut() = NSPoint::new(0.0, 0.0);
 
-                    // Get the magnification factor from the gesture recognizer
-                    let magnification: f64 = msg_send![recognizer, magnification];
-                    println!("DEBUG: Pinch magnification: {}", magnification);
+        // All mouse handling is now done through our CustomImageView subclass
+        // that forwards events to our AppDelegate
+        if let Some(window) = self.ivars().window.get() {
+            window.setAcceptsMouseMovedEvents(true);
+        }
+    }
 
-                    // Apply zoom change based on the base zoom level and magnification
-                    let base_zoom = *self.ivars().base_zoom_level.borrow();
-                    let new_zoom = base_zoom * (1.0 + magnification);
+    fn is_full_screen(&self) -> bool {
+        self.ivars()
+            .window
+            .get()
+            .is_some_and(|window| window.styleMask().contains(NSWindowStyleMask::FullScreen))
+    }
 
-                    // Update state with new zoom level
-                    self.ivars().state.borrow_mut().zoom_level = new_zoom.max(0.1).min(10.0);
+    // Fade the bottom controls in or out
+    fn set_controls_visible(&self, visible: bool) {
+        let alpha = if visible { 1.0 } else { 0.0 };
 
-                    // Only render the viewport (not regenerate pattern)
-                    return self.render_viewport();
-                }
+        unsafe {
+            if let Some(slider) = self.ivars().zoom_slider.get() {
+                slider.animator().setAlphaValue(alpha);
+            }
+            if let Some(button) = self.ivars().sampling_button.get() {
+                button.animator().setAlphaValue(alpha);
+            }
+            if let Some(button) = self.ivars().actual_size_button.get() {
+                button.animator().setAlphaValue(alpha);
+            }
+            if let Some(label) = self.ivars().status_label.get() {
+                label.animator().setAlphaValue(alpha);
             }
-
-            Bool::NO
         }
     }
-);
 
-// Implement custom methods for AppDelegate
-impl AppDelegate {
-    fn new(mtm: MainThreadMarker) -> Retained<Self> {
-        let ivars = AppDelegateIvars {
-            base_zoom_level: RefCell::new(1.0),
-            state: RefCell::new(AppState::default()),
-            cached_pattern: RefCell::new(None),
-            ..Default::default()
-        };
-        let this = Self::alloc(mtm).set_ivars(ivars);
-        unsafe { msg_send![super(this), init] }
+    // Show the controls on mouse activity and, in full screen, restart the
+    // countdown to hiding them again
+    fn reset_idle_timer(&self) {
+        if let Some(timer) = self.ivars().idle_timer.borrow_mut().take() {
+            unsafe { timer.invalidate() };
+        }
+
+        self.set_controls_visible(true);
+
+        if self.ivars().state.borrow().auto_hide_controls && self.is_full_screen() {
+            let timer = unsafe {
+                NSTimer::scheduledTimerWithTimeInterval_target_selector_userInfo_repeats(
+                    CONTROLS_IDLE_HIDE_DELAY,
+                    self.as_ref(),
+                    sel!(hideIdleControls:),
+                    None,
+                    false,
+                )
+            };
+            *self.ivars().idle_timer.borrow_mut() = Some(timer);
+        }
     }
 
-    fn create_window(&self, mtm: MainThreadMarker) -> Retained<NSWindow> {
-        let window_frame = NSRect::new(NSPoint::new(100., 100.), NSSize::new(800., 600.));
-        let style = NSWindowStyleMask::Titled
-            | NSWindowStyleMask::Closable
-            | NSWindowStyleMask::Resizable
-            | NSWindowStyleMask::Miniaturizable;
+    // Set one of the two checkerboard colors and regenerate the pattern
+    fn set_checker_color(&self, index: usize, color: [u8; 4]) -> Bool {
+        if index >= 2 {
+            return Bool::NO;
+        }
 
-        let window = unsafe {
-            NSWindow::initWithContentRect_styleMask_backing_defer(
-                NSWindow::alloc(mtm),
-                window_frame,
-                style,
-                NSBackingStoreType::Buffered,
-                false,
-            )
-        };
+        self.ivars().state.borrow_mut().checker_colors[index] = color;
+        self.save_preferences();
 
-        // Important: prevent automatic closing from releasing the window
-        // This is needed when not using a window controller
-        unsafe { window.setReleasedWhenClosed(false) };
+        // Full render (will regenerate pattern since colors changed)
+        self.render_ui()
+    }
 
-        window
+    // Central render function that updates UI based on state
+    fn render_ui(&self) -> Bool {
+        // First ensure we have the right pattern cached
+        self.ensure_pattern_cache();
+
+        // Then render the viewport based on current view parameters
+        self.render_viewport()
     }
 
-    fn setup_image_view(&self, window: &NSWindow, mtm: MainThreadMarker) {
-        let content_view = window.contentView().unwrap();
-        let content_frame = content_view.bounds();
+    // Ensure the pattern cache is up to date
+    fn ensure_pattern_cache(&self) -> Bool {
+        let state = self.ivars().state.borrow();
+        let cache = self.ivars().cached_pattern.borrow();
 
-        // Calculate the main view frame, leaving room for controls at the bottom
-        let controls_height = 60.0;
-        let main_view_frame = NSRect::new(
-            NSPoint::new(0.0, controls_height),
-            NSSize::new(
-                content_frame.size.width,
-                content_frame.size.height - controls_height,
-            ),
-        );
+        // Check if we need to regenerate the pattern
+        let regenerate = match &*cache {
+            None => true,
+            Some(cached) => {
+                cached.pattern_type != state.pattern_type
+                    || cached.primary_text != state.primary_text
+                    || cached.secondary_text != state.secondary_text
+                    || cached.checker_colors != state.checker_colors
+                    || cached.text_scale != state.text_scale
+                    || cached.text_direction != state.text_direction
+                    || cached.source_width != state.source_width
+                    || cached.source_height != state.source_height
+            }
+        };
 
-        // Create a scroll view
-        let scroll_view =
-            unsafe { NSScrollView::initWithFrame(NSScrollView::alloc(mtm), main_view_frame) };
+        if regenerate {
+            // Generate new pattern and store in cache
+            drop(cache); // Release the borrowed reference
 
-        unsafe {
-            scroll_view.setHasVerticalScroller(true);
-            scroll_view.setHasHorizontalScroller(true);
-            scroll_view.setAutoresizingMask(
-                NSAutoresizingMaskOptions::ViewWidthSizable
-                    | NSAutoresizingMaskOptions::ViewHeightSizable,
-            );
+            let source_pattern = self.generate_source_pattern_from_state(&*state);
+            *self.ivars().cached_pattern.borrow_mut() = Some(CachedSourcePattern {
+                pattern: source_pattern,
+                pattern_type: state.pattern_type.clone(),
+                primary_text: state.primary_text.clone(),
+                secondary_text: state.secondary_text.clone(),
+                checker_colors: state.checker_colors,
+                text_scale: state.text_scale,
+                text_direction: state.text_direction,
+                source_width: state.source_width,
+                source_height: state.source_height,
+            });
+        }
 
-            // Create our custom image view for the document view
-            let frame = NSRect::ZERO;
-            let new_image_view = CustomImageView::new(mtm, frame);
+        Bool::YES
+    }
 
-            // Configure image view properties
-            new_image_view.setImageScaling(NSImageScaling::ScaleProportionallyDown);
+    // Encode the currently displayed viewport image as PNG data
+    fn current_view_png(&self) -> Option<Retained<NSData>> {
+        let image = self.ivars().decoded_image.borrow().clone()?;
 
-            // Create and configure the magnification gesture recognizer for pinch-to-zoom
-            let recognizer = NSMagnificationGestureRecognizer::alloc(mtm);
-            let recognizer: Retained<NSMagnificationGestureRecognizer> =
-                msg_send![recognizer, init];
+        unsafe {
+            let tiff = image.TIFFRepresentation()?;
+            let rep = NSBitmapImageRep::imageRepWithData(&tiff)?;
+            rep.representationUsingType_properties(NSBitmapImageFileType::PNG, &NSDictionary::new())
+        }
+    }
 
-            // Set the action and target for the gesture recognizer
-            recognizer.setAction(Some(sel!(handlePinchGesture:)));
-            let target: Option<&AnyObject> = Some(self.as_ref());
-            recognizer.setTarget(target);
+    // Save the current view as a timestamped PNG on the Desktop without
+    // prompting, falling back to the Save panel if the Desktop isn't writable
+    fn save_screenshot_to_desktop(&self) -> Bool {
+        let png_data = match self.current_view_png() {
+            Some(data) => data,
+            None => {
+                self.set_status("Nothing to save");
+                return Bool::NO;
+            }
+        };
 
-            // Add the gesture recognizer to the image view
-            let view_ref: &AnyObject = new_image_view.as_ref();
-            let _: () = msg_send![view_ref, addGestureRecognizer: &*recognizer];
+        let file_name = screenshot_file_name();
+        let home = unsafe { NSHomeDirectory() }.to_string();
+        let path = Path::new(&home).join("Desktop").join(&file_name);
 
-            // Store the gesture recognizer
-            let _ = self.ivars().magnification_recognizer.set(recognizer);
+        match std::fs::write(&path, png_data.to_vec()) {
+            Ok(()) => {
+                self.set_status(&format!("Saved {}", path.display()));
+                Bool::YES
+            }
+            Err(err) => {
+                println!("DEBUG: Could not write {}: {}", path.display(), err);
+                self.save_png_with_panel(&png_data, &file_name)
+            }
+        }
+    }
 
-            // Set the image view as the document view
-            scroll_view.setDocumentView(Some(&*new_image_view));
+    // Print the whole source pattern with the current filters applied. By
+    // default it is scaled down to fit on one page; at actual size it is
+    // printed at one pixel per point and split across as many pages as needed.
+    fn print_image(&self, actual_size: bool) -> Bool {
+        let _ = self.ensure_pattern_cache();
+
+        let image = {
+            let state = self.ivars().state.borrow();
+            let cache = self.ivars().cached_pattern.borrow();
+            let Some(cached) = &*cache else {
+                return Bool::NO;
+            };
+            let (width, height) = (cached.pattern.width, cached.pattern.height);
+            let pixels = render_pixels(
+                &cached.pattern,
+                1.0,
+                0.0,
+                0.0,
+                (width, height),
+                RenderOptions {
+                    borders_only: false,
+                    sampling: SamplingMode::Nearest,
+                    ..state.render_options()
+                },
+                &state.active_filters(),
+            );
+            // Printed at one pixel per point, whatever the display's scale
+            self.generate_viewport_image(&pixels, width, height, 1.0)
+        };
+        let Some(image) = image else {
+            self.set_status("Nothing to print");
+            return Bool::NO;
+        };
 
-            // Add the scroll view to the content view
-            content_view.addSubview(&scroll_view);
+        let mtm = self.mtm();
 
-            // Store the views
-            let _ = self.ivars().scroll_view.set(scroll_view.clone());
-            let _ = self.ivars().image_view.set(new_image_view.clone());
+        unsafe {
+            let frame = NSRect::new(NSPoint::new(0.0, 0.0), image.size());
+            let view = NSImageView::initWithFrame(NSImageView::alloc(mtm), frame);
+            view.setImage(Some(&image));
+            view.setImageScaling(NSImageScaling::ScaleNone);
+
+            let operation = NSPrintOperation::printOperationWithView(&view);
+            let print_info = operation.printInfo();
+            let pagination = if actual_size {
+                NSPrintingPaginationMode::Automatic
+            } else {
+                NSPrintingPaginationMode::Fit
+            };
+            print_info.setHorizontalPagination(pagination);
+            print_info.setVerticalPagination(pagination);
+            print_info.setHorizontallyCentered(true);
+            print_info.setVerticallyCentered(true);
+
+            Bool::new(operation.runOperation())
         }
     }
 
-    fn setup_zoom_controls(&self, window: &NSWindow, mtm: MainThreadMarker) {
-        let content_view = window.contentView().unwrap();
+    // Save the whole source as PNG through the Save panel, at the current zoom
+    // or, if chosen in the panel, at one pixel per source pixel. Pan doesn't
+    // matter; the export always starts at the source origin.
+    fn save_image(&self) -> Bool {
+        let mtm = self.mtm();
+        let _ = self.ensure_pattern_cache();
+
+        let suggested_name = match &self.ivars().state.borrow().file_name {
+            Some(file_name) => match Path::new(file_name).file_stem() {
+                Some(stem) => format!("{}.png", stem.to_string_lossy()),
+                None => screenshot_file_name(),
+            },
+            None => screenshot_file_name(),
+        };
 
-        // Create a slider for zoom control
-        let slider_frame = NSRect::new(NSPoint::new(530., 25.), NSSize::new(180., 30.));
-        let slider = unsafe { NSSlider::initWithFrame(NSSlider::alloc(mtm), slider_frame) };
+        let panel = unsafe { NSSavePanel::savePanel(mtm) };
+        let native_checkbox = unsafe {
+            NSButton::checkboxWithTitle_target_action(
+                ns_string!("Export at native resolution"),
+                None,
+                None,
+                mtm,
+            )
+        };
 
-        unsafe {
-            // Configure slider properties
-            slider.setMinValue(0.1);
-            slider.setMaxValue(5.0);
-            slider.setDoubleValue(1.0);
+        let url = unsafe {
+            if let Some(png_type) = UTType::typeWithFilenameExtension(ns_string!("png")) {
+                let allowed_types = NSArray::from_slice(&[&*png_type]);
+                panel.setAllowedContentTypes(&allowed_types);
+            }
+            panel.setNameFieldStringValue(&NSString::from_str(&suggested_name));
+            panel.setAccessoryView(Some(&native_checkbox));
 
-            // Set number of tick marks directly using msg_send - use i64 (long) instead of i32
-            let _: () = msg_send![&*slider, setNumberOfTickMarks: 9i64];
-            let _: () = msg_send![&*slider, setAllowsTickMarkValuesOnly: false];
+            if panel.runModal() != 1 {
+                return Bool::NO;
+            }
+            panel.URL()
+        };
+        let Some(url) = url else {
+            return Bool::NO;
+        };
 
-            // Set action and target
-            slider.setAction(Some(sel!(zoomChanged:)));
-            let target: Option<&AnyObject> = Some(self.as_ref());
-            slider.setTarget(target);
+        let png_data = {
+            let state = self.ivars().state.borrow();
+            let cache = self.ivars().cached_pattern.borrow();
+            let Some(cached) = &*cache else {
+                return Bool::NO;
+            };
 
-            // Add to content view
-            content_view.addSubview(&slider);
+            let zoom = if unsafe { native_checkbox.state() } == NSControlStateValueOn {
+                1.0
+            } else {
+                state.zoom_level
+            };
+            let (width, height) = scaled_source_size(&cached.pattern, zoom);
+            let pixels = render_pixels(
+                &cached.pattern,
+                zoom,
+                0.0,
+                0.0,
+                (width, height),
+                state.render_options(),
+                &state.active_filters(),
+            );
+            rgba_to_png(&pixels, width, height)
+        };
 
-            // Store the slider
-            let _ = self.ivars().zoom_slider.set(slider.clone());
+        match png_data {
+            Some(png_data) if unsafe { png_data.writeToURL_atomically(&url, true) } => {
+                self.set_status(&format!(
+                    "Saved {}",
+                    unsafe { url.path() }.unwrap_or_default()
+                ));
+                Bool::YES
+            }
+            _ => {
+                self.set_status("Failed to save image");
+                Bool::NO
+            }
         }
     }
 
-    fn add_buttons(&self, window: &NSWindow, mtm: MainThreadMarker) {
-        // Create Open JP2 button
-        let open_button_frame = NSRect::new(NSPoint::new(20., 20.), NSSize::new(100., 30.));
-        let open_button =
-            unsafe { NSButton::initWithFrame(NSButton::alloc(mtm), open_button_frame) };
+    // Ask the user where to save PNG data using the standard Save panel
+    fn save_png_with_panel(&self, png_data: &NSData, suggested_name: &str) -> Bool {
+        let mtm = self.mtm();
+        let panel = unsafe { NSSavePanel::savePanel(mtm) };
 
         unsafe {
-            open_button.setTitle(ns_string!("Open JP2"));
-            open_button.setBezelStyle(NSBezelStyle::Automatic);
-            open_button.setAction(Some(sel!(openFile:)));
+            if let Some(png_type) = UTType::typeWithFilenameExtension(ns_string!("png")) {
+                let allowed_types = NSArray::from_slice(&[&*png_type]);
+                panel.setAllowedContentTypes(&allowed_types);
+            }
+            panel.setNameFieldStringValue(&NSString::from_str(suggested_name));
 
-            // Convert self to AnyObject for target
-            let target: Option<&AnyObject> = Some(self.as_ref());
-            open_button.setTarget(target);
+            if panel.runModal() == 1 {
+                if let Some(url) = panel.URL() {
+                    if png_data.writeToURL_atomically(&url, true) {
+                        self.set_status(&format!("Saved {}", url.path().unwrap_or_default()));
+                        return Bool::YES;
+                    }
+
+                    self.set_status("Failed to save image");
+                }
+            }
+        }
+
+        Bool::NO
+    }
 
-            let content_view = window.contentView().unwrap();
-            content_view.addSubview(&open_button);
-        }
+    // Flip between the full pattern and just its debug borders
+    fn toggle_borders_only(&self) -> Bool {
+        let borders_only = {
+            let mut state = self.ivars().state.borrow_mut();
+            state.borders_only = !state.borders_only;
+            state.borders_only
+        };
 
-        // Create Gradient button
-        let gradient_button_frame = NSRect::new(NSPoint::new(140., 20.), NSSize::new(100., 30.));
-        let gradient_button =
-            unsafe { NSButton::initWithFrame(NSButton::alloc(mtm), gradient_button_frame) };
+        self.set_status(if borders_only {
+            "Showing debug borders only"
+        } else {
+            "Showing full image"
+        });
+        self.render_viewport()
+    }
 
-        unsafe {
-            gradient_button.setTitle(ns_string!("Gradient"));
-            gradient_button.setBezelStyle(NSBezelStyle::Automatic);
-            gradient_button.setAction(Some(sel!(createGradient:)));
+    // Switch between nearest-neighbor and supersampled rendering, reporting
+    // how far apart the two renders of the current view are so it's easy to
+    // judge whether supersampling is worth its cost here
+    fn toggle_sampling(&self) -> Bool {
+        let _ = self.ensure_pattern_cache();
+        let (previous, state) = {
+            let mut state = self.ivars().state.borrow_mut();
+            let previous = state.sampling;
+            state.sampling = previous.next();
+            (previous, state.clone())
+        };
+        if let Some(button) = self.ivars().sampling_button.get() {
+            unsafe { button.setTitle(&NSString::from_str(state.sampling.button_title())) };
+        }
 
-            // Convert self to AnyObject for target
-            let target: Option<&AnyObject> = Some(self.as_ref());
-            gradient_button.setTarget(target);
+        let difference = self.ivars().cached_pattern.borrow().as_ref().map(|cached| {
+            let pattern = &cached.pattern;
+            let zoom = state.zoom_level;
+            let (view_x, view_y) = state.render_origin();
+            let out_width = (pattern.width as f64 * zoom) as usize;
+            let out_height = (pattern.height as f64 * zoom) as usize;
 
-            let content_view = window.contentView().unwrap();
-            content_view.addSubview(&gradient_button);
+            let [before, after] = [previous, state.sampling].map(|sampling| {
+                let render = region_renderer(sampling);
+                render(pattern, zoom, view_x, view_y, out_width, out_height)
+            });
+            pixel_difference(&before, &after)
+        });
+
+        let mode = state.sampling.title();
+        match difference {
+            Some((max, mean)) => self.set_status(&format!(
+                "Sampling: {} (vs. {}: max difference {}, mean {:.2})",
+                mode,
+                previous.title(),
+                max,
+                mean
+            )),
+            None => self.set_status(&format!("Sampling: {}", mode)),
         }
+        self.render_viewport()
+    }
 
-        // Create Checkerboard button
-        let checkerboard_button_frame =
-            NSRect::new(NSPoint::new(260., 20.), NSSize::new(100., 30.));
-        let checkerboard_button =
-            unsafe { NSButton::initWithFrame(NSButton::alloc(mtm), checkerboard_button_frame) };
+    // Pin the part of the source currently on screen, clipped to the source,
+    // or release an existing pin. Zooming or panning also releases it.
+    fn toggle_pinned_region(&self) -> Bool {
+        let Some((viewport_width, viewport_height)) = self.viewport_size() else {
+            return Bool::NO;
+        };
 
-        unsafe {
-            checkerboard_button.setTitle(ns_string!("Checkerboard"));
-            checkerboard_button.setBezelStyle(NSBezelStyle::Automatic);
-            checkerboard_button.setAction(Some(sel!(createCheckerboard:)));
+        let pinned = {
+            let mut state = self.ivars().state.borrow_mut();
+            if state.pinned_region.is_some() {
+                state.pinned_region = None;
+                None
+            } else {
+                let (x, y, width, height) =
+                    state.visible_source_rect(viewport_width, viewport_height);
+                let left = x.max(0.0);
+                let top = y.max(0.0);
+                let right = (x + width).min(state.source_width as f64);
+                let bottom = (y + height).min(state.source_height as f64);
+                if right <= left || bottom <= top {
+                    return Bool::NO;
+                }
 
-            // Convert self to AnyObject for target
-            let target: Option<&AnyObject> = Some(self.as_ref());
-            checkerboard_button.setTarget(target);
+                state.pinned_region = Some((left, top, right - left, bottom - top));
+                state.pinned_region
+            }
+        };
 
-            let content_view = window.contentView().unwrap();
-            content_view.addSubview(&checkerboard_button);
+        match pinned {
+            Some((x, y, width, height)) => self.set_status(&format!(
+                "Pinned region {:.0}, {:.0}, {:.0}×{:.0}",
+                x, y, width, height
+            )),
+            None => self.set_status("Region unpinned"),
         }
+        self.render_viewport()
     }
 
-    fn setup_mouse_handling(&self, _window: &NSWindow) {
-        // Initial values
-        *self.ivars().is_panning.borrow_mut() = false;
-        *self.ivars().last_mouse_location.borrow_mut() = NSPoint::new(0.0, 0.0);
+    fn toggle_rulers(&self) -> Bool {
+        let show_rulers = {
+            let mut state = self.ivars().state.borrow_mut();
+            state.show_rulers = !state.show_rulers;
+            state.show_rulers
+        };
 
-        // All mouse handling is now done through our CustomImageView subclass
-        // that forwards events to our AppDelegate
-        if let Some(window) = self.ivars().window.get() {
-            window.setAcceptsMouseMovedEvents(true);
-        }
+        self.set_status(if show_rulers {
+            "Showing rulers"
+        } else {
+            "Rulers hidden"
+        });
+        self.render_viewport()
     }
 
-    // Central render function that updates UI based on state
-    fn render_ui(&self) -> Bool {
-        // First ensure we have the right pattern cached
-        self.ensure_pattern_cache();
+    // Move the view by whole source pixels (one source pixel spans `zoom`
+    // viewport pixels), within the same limits as dragging, and report the
+    // resulting offset
+    fn nudge_pan(&self, step_x: f64, step_y: f64) -> Bool {
+        let (offset_x, offset_y) = {
+            let mut state = self.ivars().state.borrow_mut();
+            state.pinned_region = None;
+            let zoom = state.zoom_level;
+            let (view_x, view_y) = (state.view_x + step_x * zoom, state.view_y + step_y * zoom);
+            state.set_pan(view_x, view_y);
+            (state.view_x / zoom, state.view_y / zoom)
+        };
 
-        // Then render the viewport based on current view parameters
+        self.set_status(&format!(
+            "Pan offset: {:.2}, {:.2} source px",
+            offset_x, offset_y
+        ));
         self.render_viewport()
     }
 
-    // Ensure the pattern cache is up to date
-    fn ensure_pattern_cache(&self) -> Bool {
-        let state = self.ivars().state.borrow();
-        let cache = self.ivars().cached_pattern.borrow();
-
-        // Check if we need to regenerate the pattern
-        let regenerate = match &*cache {
-            None => true,
-            Some(cached) => {
-                cached.pattern_type != state.pattern_type
-                    || cached.primary_text != state.primary_text
-                    || cached.secondary_text != state.secondary_text
-                    || cached.source_width != state.source_width
-                    || cached.source_height != state.source_height
-            }
+    // Freeze or unfreeze the displayed image. The window title carries a
+    // marker while frozen so it's obvious the view isn't live.
+    fn toggle_freeze(&self) -> Bool {
+        let frozen = {
+            let mut state = self.ivars().state.borrow_mut();
+            state.frozen = !state.frozen;
+            state.frozen
         };
 
-        if regenerate {
-            // Generate new pattern and store in cache
-            drop(cache); // Release the borrowed reference
-
-            let source_pattern = self.generate_source_pattern_from_state(&*state);
-            *self.ivars().cached_pattern.borrow_mut() = Some(CachedSourcePattern {
-                pattern: source_pattern,
-                pattern_type: state.pattern_type.clone(),
-                primary_text: state.primary_text.clone(),
-                secondary_text: state.secondary_text.clone(),
-                source_width: state.source_width,
-                source_height: state.source_height,
+        if let Some(window) = self.ivars().window.get() {
+            window.setTitle(if frozen {
+                ns_string!("JP2 Viewer (Frozen)")
+            } else {
+                ns_string!("JP2 Viewer")
             });
         }
 
-        Bool::YES
+        if frozen {
+            self.set_status("View frozen, press F to resume");
+            Bool::YES
+        } else {
+            self.set_status("View unfrozen");
+            self.render_ui()
+        }
     }
 
     // Render the viewport based on current view parameters
     fn render_viewport(&self) -> Bool {
+        if let Some((width, height)) = self.viewport_size() {
+            let mut state = self.ivars().state.borrow_mut();
+            state.viewport_size = (width, height);
+            state.frame_pinned_region(width, height);
+        }
+
         let state = self.ivars().state.borrow();
         let cache = self.ivars().cached_pattern.borrow();
 
+        if state.frozen {
+            return Bool::NO;
+        }
+
         if let Some(cached_pattern) = &*cache {
+            let (view_x, view_y) = state.render_origin();
+
+            // Only the part of the scaled source that's on screen is
+            // rendered, so memory doesn't grow with the zoom level
+            let (scaled_width, scaled_height) =
+                scaled_source_size(&cached_pattern.pattern, state.zoom_level);
+            let (viewport_width, viewport_height) = state.viewport_size;
+            let width = scaled_width.min(viewport_width as usize);
+            let height = scaled_height.min(viewport_height as usize);
+
             // Create viewport image by transforming the cached source pattern
-            if let Some(image) = self.generate_viewport_image(
+            let pixels = render_pixels(
                 &cached_pattern.pattern,
                 state.zoom_level,
-                state.view_x,
-                state.view_y,
-            ) {
+                view_x,
+                view_y,
+                (width, height),
+                state.render_options(),
+                &state.active_filters(),
+            );
+            let scale = self.backing_scale();
+            if let Some(image) = self.generate_viewport_image(&pixels, width, height, scale) {
                 // Store the generated image
                 *self.ivars().decoded_image.borrow_mut() = Some(image.clone());
 
@@ -972,8 +5786,7 @@ impl AppDelegate {
                     unsafe {
                         image_view.setImage(Some(&image));
 
-                        let image_size = image.size();
-                        let frame = NSRect::new(NSPoint::new(0.0, 0.0), image_size);
+                        let frame = NSRect::new(NSPoint::new(0.0, 0.0), image.size());
                         image_view.setFrame(frame);
                     }
                 }
@@ -988,6 +5801,7 @@ impl AppDelegate {
                         scroll_view.setNeedsDisplay(true);
                     }
                 }
+                self.center_image_view();
 
                 // Update zoom slider to match current zoom level
                 if let Some(slider) = self.ivars().zoom_slider.get() {
@@ -995,6 +5809,7 @@ impl AppDelegate {
                         slider.setDoubleValue(state.zoom_level);
                     }
                 }
+                self.sync_zoom_preset_popup();
 
                 return Bool::YES;
             }
@@ -1003,162 +5818,713 @@ impl AppDelegate {
         Bool::NO
     }
 
-    // Generate viewport image from source pattern
+    // Center the image view in the scroll view along any axis where it's
+    // smaller than the visible area, rather than leaving it in the corner
+    fn center_image_view(&self) {
+        let (Some(scroll_view), Some(image_view)) = (
+            self.ivars().scroll_view.get(),
+            self.ivars().image_view.get(),
+        ) else {
+            return;
+        };
+
+        let content_size = unsafe { scroll_view.contentSize() };
+        let size = image_view.frame().size;
+        let origin = NSPoint::new(
+            ((content_size.width - size.width) / 2.0).max(0.0).floor(),
+            ((content_size.height - size.height) / 2.0).max(0.0).floor(),
+        );
+        unsafe { image_view.setFrameOrigin(origin) };
+    }
+
+    // Wrap rendered viewport pixels in an image sized in points at `scale`
+    // pixels per point
     fn generate_viewport_image(
         &self,
-        source_pattern: &SourcePattern,
-        zoom_level: f64,
-        view_x: f64,
-        view_y: f64,
+        pixels: &[u8],
+        viewport_width: usize,
+        viewport_height: usize,
+        scale: f64,
     ) -> Option<Retained<NSImage>> {
-        // Viewport dimensions based on source dimensions and zoom level
-        let viewport_width = (source_pattern.width as f64 * zoom_level) as usize;
-        let viewport_height = (source_pattern.height as f64 * zoom_level) as usize;
-
-        // Create a new image of the viewport size
-        let size = NSSize::new(viewport_width as f64, viewport_height as f64);
+        // The live view passes the backing scale, so each pixel is drawn to
+        // one backing pixel and 100% zoom is actual size
+        let size = NSSize::new(
+            viewport_width as f64 / scale,
+            viewport_height as f64 / scale,
+        );
         let alloc = NSImage::alloc();
         let image = unsafe { NSImage::initWithSize(alloc, size) };
 
         // Create a bitmap representation
-        let alloc = NSBitmapImageRep::alloc();
-        let color_space_name = ns_string!("NSDeviceRGBColorSpace");
-        let bits_per_component = 8;
-        let bytes_per_row = viewport_width * 4;
-
-        let rep = unsafe {
-            let planes: *const *mut u8 = std::ptr::null();
-            let rep: Retained<NSBitmapImageRep> = msg_send![alloc,
-                initWithBitmapDataPlanes: planes,
-                pixelsWide: viewport_width as isize,
-                pixelsHigh: viewport_height as isize,
-                bitsPerSample: bits_per_component as isize,
-                samplesPerPixel: 4 as isize,
-                hasAlpha: true,
-                isPlanar: false,
-                colorSpaceName: &*color_space_name,
-                bytesPerRow: bytes_per_row as isize,
-                bitsPerPixel: 32 as isize
-            ];
-
-            rep
-        };
+        let rep = new_rgba_bitmap(viewport_width, viewport_height);
 
         // Get bitmap data buffer
         let buffer: *mut u8 = unsafe { msg_send![&*rep, bitmapData] };
 
-        if buffer.is_null() {
+        if buffer.is_null() || pixels.len() != viewport_width * viewport_height * 4 {
             println!("Failed to get bitmap data");
             return None;
         }
+        unsafe { std::ptr::copy_nonoverlapping(pixels.as_ptr(), buffer, pixels.len()) };
+
+        // Add the bitmap representation to the image
+        unsafe { image.addRepresentation(&rep) };
+
+        Some(image)
+    }
+
+    // Make a custom generator available as a pattern, returning the
+    // PatternType to put in AppState to display it
+    fn register_pattern_generator(&self, generator: Rc<dyn PatternGenerator>) -> PatternType {
+        let mut generators = self.ivars().custom_patterns.borrow_mut();
+        generators.push(generator);
+        PatternType::Custom(generators.len() - 1)
+    }
+
+    // Run the render benchmark on a background thread and save the report on
+    // the Desktop; a timer picks up the outcome on the main thread
+    fn export_render_stats(&self) -> Bool {
+        if self.ivars().render_stats_job.borrow().is_some() {
+            self.set_status("Render stats are already being measured");
+            return Bool::NO;
+        }
+
+        let file_name = screenshot_file_name().replace(".png", "-render-stats.json");
+        let home = unsafe { NSHomeDirectory() }.to_string();
+        let path = Path::new(&home).join("Desktop").join(file_name);
+
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let report = render_benchmark_report(BENCH_DEFAULT_ITERATIONS);
+            let message = match std::fs::write(&path, report) {
+                Ok(()) => format!("Saved {}", path.display()),
+                Err(err) => format!("Couldn't write {}: {}", path.display(), err),
+            };
+            let _ = sender.send(message);
+        });
 
-        // Apply zooming and panning to source pattern to generate final image
+        *self.ivars().render_stats_job.borrow_mut() = Some(receiver);
+        self.set_status("Measuring render stats…");
         unsafe {
-            let scale_factor = 1.0 / zoom_level;
-            let start_src_x = (view_x * scale_factor) as usize;
-            let start_src_y = (view_y * scale_factor) as usize;
+            NSTimer::scheduledTimerWithTimeInterval_target_selector_userInfo_repeats(
+                RENDER_STATS_POLL_INTERVAL,
+                self.as_ref(),
+                sel!(checkRenderStats:),
+                None,
+                true,
+            )
+        };
+        Bool::YES
+    }
+
+    // Generate source pattern based solely on state
+    fn generate_source_pattern_from_state(&self, state: &AppState) -> SourcePattern {
+        generate_source_pattern(state, &self.ivars().custom_patterns.borrow())
+    }
+}
+
+// Time pattern generation and rendering for each BENCH_RENDERS case,
+// returning a JSON report with min/median/max milliseconds for both. The
+// cases only use built-in patterns, so this can run off the main thread.
+fn render_benchmark_report(iterations: usize) -> String {
+    let iterations = iterations.max(1);
+    let mut cases = Vec::new();
+
+    for bench in &BENCH_RENDERS {
+        let state = AppState {
+            pattern_type: bench.pattern_type.clone(),
+            source_width: bench.size.0,
+            source_height: bench.size.1,
+            secondary_text: Some(GOLDEN_FILE_NAME.to_string()),
+            ..AppState::default()
+        };
+        let options = RenderOptions {
+            sampling: bench.sampling,
+            ..RenderOptions::default()
+        };
+
+        let mut generate_ms = Vec::with_capacity(iterations);
+        let mut render_ms = Vec::with_capacity(iterations);
+        for _ in 0..iterations {
+            let start = Instant::now();
+            let pattern = generate_source_pattern(&state, &[]);
+            generate_ms.push(start.elapsed().as_secs_f64() * 1000.0);
+
+            let start = Instant::now();
+            let size = scaled_source_size(&pattern, bench.zoom_level);
+            let pixels = render_pixels(&pattern, bench.zoom_level, 0.0, 0.0, size, options, &[]);
+            render_ms.push(start.elapsed().as_secs_f64() * 1000.0);
+            std::hint::black_box(pixels);
+        }
 
-            for y in 0..viewport_height {
-                for x in 0..viewport_width {
-                    let dst_idx = (y * bytes_per_row + x * 4) as isize;
+        let (generate_min, generate_median, generate_max) = timing_stats(&mut generate_ms);
+        let (render_min, render_median, render_max) = timing_stats(&mut render_ms);
+        println!(
+            "BENCH: {} generate {:.2}ms render {:.2}ms (median)",
+            bench.name, generate_median, render_median
+        );
+        cases.push(format!(
+            concat!(
+                "    {{\"name\": \"{}\", \"width\": {}, \"height\": {}, ",
+                "\"zoom\": {}, \"sampling\": \"{}\",\n",
+                "     \"generate_ms\": {{\"min\": {:.3}, \"median\": {:.3}, \"max\": {:.3}}},\n",
+                "     \"render_ms\": {{\"min\": {:.3}, \"median\": {:.3}, \"max\": {:.3}}}}}"
+            ),
+            bench.name,
+            bench.size.0,
+            bench.size.1,
+            bench.zoom_level,
+            bench.sampling.title(),
+            generate_min,
+            generate_median,
+            generate_max,
+            render_min,
+            render_median,
+            render_max
+        ));
+    }
 
-                    // Map viewport position to source coordinates
-                    let src_x = start_src_x + (x as f64 * scale_factor) as usize;
-                    let src_y = start_src_y + (y as f64 * scale_factor) as usize;
+    format!(
+        "{{\n  \"version\": \"{}\",\n  \"iterations\": {},\n  \"renders\": [\n{}\n  ]\n}}\n",
+        env!("CARGO_PKG_VERSION"),
+        iterations,
+        cases.join(",\n")
+    )
+}
 
-                    // Clamp to valid range
-                    let src_x_clamped = src_x.min(source_pattern.width - 1);
-                    let src_y_clamped = src_y.min(source_pattern.height - 1);
+// Generate the source pattern described by state. Custom patterns index into
+// custom_patterns, the generators registered with the app delegate.
+fn generate_source_pattern(
+    state: &AppState,
+    custom_patterns: &[Rc<dyn PatternGenerator>],
+) -> SourcePattern {
+    let width = state.source_width;
+    let height = state.source_height;
+    let bytes_per_row = width * 4;
+    let buffer_size = bytes_per_row * height;
+    let mut buffer = vec![0; buffer_size];
+
+    let generator: Rc<dyn PatternGenerator + '_> = match &state.pattern_type {
+        PatternType::Checkerboard => Rc::new(CheckerboardPattern {
+            colors: state.checker_colors,
+        }),
+        PatternType::Gradient => Rc::new(GradientPattern),
+        PatternType::Text => Rc::new(TextPattern {
+            primary_text: state.primary_text.as_deref(),
+            secondary_text: state.secondary_text.as_deref(),
+            text_scale: state.text_scale,
+            direction: state.text_direction,
+        }),
+        PatternType::Custom(index) => match custom_patterns.get(*index) {
+            Some(generator) => generator.clone(),
+            None => Rc::new(GradientPattern),
+        },
+        PatternType::Image {
+            rgba,
+            width: image_width,
+            height: image_height,
+        } => Rc::new(ImagePattern {
+            rgba,
+            width: *image_width,
+            height: *image_height,
+        }),
+    };
+    generator.generate(&mut buffer, width, height, bytes_per_row);
+
+    SourcePattern {
+        buffer,
+        width,
+        height,
+        bytes_per_row,
+    }
+}
 
-                    let src_idx = src_y_clamped * source_pattern.bytes_per_row + src_x_clamped * 4;
+// Size of the whole source scaled by zoom_level, in viewport pixels
+fn scaled_source_size(source_pattern: &SourcePattern, zoom_level: f64) -> (usize, usize) {
+    (
+        (source_pattern.width as f64 * zoom_level) as usize,
+        (source_pattern.height as f64 * zoom_level) as usize,
+    )
+}
 
-                    if src_idx + 3 < source_pattern.buffer.len() {
-                        *buffer.offset(dst_idx) = source_pattern.buffer[src_idx];
-                        *buffer.offset(dst_idx + 1) = source_pattern.buffer[src_idx + 1];
-                        *buffer.offset(dst_idx + 2) = source_pattern.buffer[src_idx + 2];
-                        *buffer.offset(dst_idx + 3) = source_pattern.buffer[src_idx + 3];
-                    } else {
-                        // Out of bounds - use purple
-                        *buffer.offset(dst_idx) = 128;
-                        *buffer.offset(dst_idx + 1) = 0;
-                        *buffer.offset(dst_idx + 2) = 128;
-                        *buffer.offset(dst_idx + 3) = 255;
+// The full viewport pipeline (sampling, overlays, filters) as a tightly
+// packed RGBA buffer of `size` pixels, starting at the view offset
+fn render_pixels(
+    source_pattern: &SourcePattern,
+    zoom_level: f64,
+    view_x: f64,
+    view_y: f64,
+    size: (usize, usize),
+    options: RenderOptions,
+    filters: &[ColorFilter],
+) -> Vec<u8> {
+    let (viewport_width, viewport_height) = size;
+
+    let render = region_renderer(options.sampling);
+    let mut pixels = render(
+        source_pattern,
+        zoom_level,
+        view_x,
+        view_y,
+        viewport_width,
+        viewport_height,
+    );
+    if options.debug_borders || options.borders_only {
+        apply_debug_borders(
+            &mut pixels,
+            source_pattern,
+            zoom_level,
+            view_x,
+            view_y,
+            options,
+        );
+    }
+    apply_color_filters(&mut pixels, filters);
+    if options.rulers {
+        apply_rulers(
+            &mut pixels,
+            viewport_width,
+            viewport_height,
+            zoom_level,
+            view_x,
+            view_y,
+        );
+    }
+
+    pixels
+}
+
+// Render a region of the source pattern into a tightly packed RGBA buffer of
+// out_width x out_height pixels. All view parameters are passed in rather than
+// read from state, so offscreen renders don't disturb the live view.
+fn render_region(
+    source_pattern: &SourcePattern,
+    zoom_level: f64,
+    view_x: f64,
+    view_y: f64,
+    out_width: usize,
+    out_height: usize,
+) -> Vec<u8> {
+    let bytes_per_row = out_width * 4;
+    let mut buffer = vec![0u8; bytes_per_row * out_height];
+
+    // The source origin keeps its fractional part, so a pan that isn't a
+    // whole number of source pixels shows only part of the first one
+    let scale_factor = 1.0 / zoom_level;
+    let start_src_x = (view_x * scale_factor).max(0.0);
+    let start_src_y = (view_y * scale_factor).max(0.0);
+
+    for y in 0..out_height {
+        for x in 0..out_width {
+            let dst_idx = px(&buffer, bytes_per_row, x, y);
+
+            // Map viewport position to source coordinates
+            let src_x = (start_src_x + x as f64 * scale_factor) as usize;
+            let src_y = (start_src_y + y as f64 * scale_factor) as usize;
+
+            // Clamp to valid range
+            let src_x_clamped = src_x.min(source_pattern.width - 1);
+            let src_y_clamped = src_y.min(source_pattern.height - 1);
+
+            let src_idx = src_y_clamped * source_pattern.bytes_per_row + src_x_clamped * 4;
+
+            if src_idx + 3 < source_pattern.buffer.len() {
+                buffer[dst_idx..dst_idx + 4]
+                    .copy_from_slice(&source_pattern.buffer[src_idx..src_idx + 4]);
+            } else {
+                // Out of bounds - use purple
+                buffer[dst_idx..dst_idx + 4].copy_from_slice(&[128, 0, 128, 255]);
+            }
+        }
+    }
+
+    buffer
+}
+
+// Like render_region, but each output pixel averages a grid of samples
+// spread across the source area it covers. Edges between contrasting
+// source pixels blend instead of snapping, at the cost of at least
+// SUPERSAMPLE_GRID² samples per pixel; below 1/SUPERSAMPLE_GRID zoom the
+// grid has a sample for every source pixel the output pixel covers.
+fn render_region_supersampled(
+    source_pattern: &SourcePattern,
+    zoom_level: f64,
+    view_x: f64,
+    view_y: f64,
+    out_width: usize,
+    out_height: usize,
+) -> Vec<u8> {
+    let bytes_per_row = out_width * 4;
+    let mut buffer = vec![0u8; bytes_per_row * out_height];
+
+    let scale_factor = 1.0 / zoom_level;
+    let start_src_x = (view_x * scale_factor).max(0.0);
+    let start_src_y = (view_y * scale_factor).max(0.0);
+    let max_x = (source_pattern.width - 1) as f64;
+    let max_y = (source_pattern.height - 1) as f64;
+    let grid = SUPERSAMPLE_GRID.max(scale_factor.ceil() as usize);
+    let sample_count = (grid * grid) as u32;
+
+    for y in 0..out_height {
+        for x in 0..out_width {
+            let mut sum = [0u32; 4];
+
+            for sy in 0..grid {
+                for sx in 0..grid {
+                    // Sample points sit at the centers of a grid over the output pixel
+                    let offset_x = (sx as f64 + 0.5) / grid as f64;
+                    let offset_y = (sy as f64 + 0.5) / grid as f64;
+                    let src_x = (start_src_x + (x as f64 + offset_x) * scale_factor).min(max_x);
+                    let src_y = (start_src_y + (y as f64 + offset_y) * scale_factor).min(max_y);
+
+                    let src_idx = px(
+                        &source_pattern.buffer,
+                        source_pattern.bytes_per_row,
+                        src_x as usize,
+                        src_y as usize,
+                    );
+                    for (channel, total) in sum.iter_mut().enumerate() {
+                        *total += source_pattern.buffer[src_idx + channel] as u32;
                     }
                 }
             }
+
+            let dst_idx = px(&buffer, bytes_per_row, x, y);
+            for (channel, total) in sum.iter().enumerate() {
+                buffer[dst_idx + channel] = (total / sample_count) as u8;
+            }
         }
+    }
 
-        // Add the bitmap representation to the image
-        unsafe { image.addRepresentation(&rep) };
+    buffer
+}
 
-        Some(image)
+// The render_region variant that implements a sampling mode
+fn region_renderer(
+    sampling: SamplingMode,
+) -> fn(&SourcePattern, f64, f64, f64, usize, usize) -> Vec<u8> {
+    match sampling {
+        SamplingMode::Nearest => render_region,
+        SamplingMode::Supersampled => render_region_supersampled,
+        SamplingMode::Bilinear => render_region_bilinear,
     }
+}
 
-    // Generate source pattern based solely on state
-    fn generate_source_pattern_from_state(&self, state: &AppState) -> SourcePattern {
-        let width = state.source_width;
-        let height = state.source_height;
-        let bytes_per_row = width * 4;
-        let buffer_size = bytes_per_row * height;
-        let mut buffer = vec![0; buffer_size];
+// Like render_region, but each output pixel interpolates between the four
+// source pixels around its center. Below 1.0 zoom an output pixel covers
+// several source pixels, so it is box-filtered by supersampling instead.
+fn render_region_bilinear(
+    source_pattern: &SourcePattern,
+    zoom_level: f64,
+    view_x: f64,
+    view_y: f64,
+    out_width: usize,
+    out_height: usize,
+) -> Vec<u8> {
+    if zoom_level < 1.0 {
+        return render_region_supersampled(
+            source_pattern,
+            zoom_level,
+            view_x,
+            view_y,
+            out_width,
+            out_height,
+        );
+    }
 
-        match state.pattern_type {
-            PatternType::Checkerboard => {
-                self.generate_checkerboard_pattern(&mut buffer, width, height, bytes_per_row)
+    let bytes_per_row = out_width * 4;
+    let mut buffer = vec![0u8; bytes_per_row * out_height];
+
+    let scale_factor = 1.0 / zoom_level;
+    let start_src_x = (view_x * scale_factor).max(0.0);
+    let start_src_y = (view_y * scale_factor).max(0.0);
+    let max_x = source_pattern.width - 1;
+    let max_y = source_pattern.height - 1;
+
+    for y in 0..out_height {
+        // Source pixel centers sit at half-pixel offsets
+        let src_y = (start_src_y + (y as f64 + 0.5) * scale_factor - 0.5).max(0.0);
+        let y0 = (src_y as usize).min(max_y);
+        let y1 = (y0 + 1).min(max_y);
+        let fy = src_y - src_y.floor();
+
+        for x in 0..out_width {
+            let src_x = (start_src_x + (x as f64 + 0.5) * scale_factor - 0.5).max(0.0);
+            let x0 = (src_x as usize).min(max_x);
+            let x1 = (x0 + 1).min(max_x);
+            let fx = src_x - src_x.floor();
+
+            let buffer_in = &source_pattern.buffer;
+            let row_bytes = source_pattern.bytes_per_row;
+            let top_left = px(buffer_in, row_bytes, x0, y0);
+            let top_right = px(buffer_in, row_bytes, x1, y0);
+            let bottom_left = px(buffer_in, row_bytes, x0, y1);
+            let bottom_right = px(buffer_in, row_bytes, x1, y1);
+
+            let dst_idx = px(&buffer, bytes_per_row, x, y);
+            for channel in 0..4 {
+                let top = buffer_in[top_left + channel] as f64 * (1.0 - fx)
+                    + buffer_in[top_right + channel] as f64 * fx;
+                let bottom = buffer_in[bottom_left + channel] as f64 * (1.0 - fx)
+                    + buffer_in[bottom_right + channel] as f64 * fx;
+                buffer[dst_idx + channel] = (top * (1.0 - fy) + bottom * fy).round() as u8;
             }
-            PatternType::Gradient => {
-                self.generate_gradient_pattern(&mut buffer, width, height, bytes_per_row)
+        }
+    }
+
+    buffer
+}
+
+// Draw the debug borders and corner markers over rendered pixels, using the
+// same mapping as render_region. They are never baked into the source, so
+// toggling them or changing the inset needs no regeneration. In
+// borders-only mode every other pixel is cleared.
+fn apply_debug_borders(
+    pixels: &mut [u8],
+    source_pattern: &SourcePattern,
+    zoom_level: f64,
+    view_x: f64,
+    view_y: f64,
+    options: RenderOptions,
+) {
+    // Same viewport width as generate_viewport_image
+    let out_width = (source_pattern.width as f64 * zoom_level) as usize;
+    let scale_factor = 1.0 / zoom_level;
+    let start_src_x = (view_x * scale_factor).max(0.0);
+    let start_src_y = (view_y * scale_factor).max(0.0);
+    let corner_alpha = if options.blend_debug_corners {
+        DEBUG_CORNER_BLEND_ALPHA
+    } else {
+        255
+    };
+
+    for (i, pixel) in pixels.chunks_exact_mut(4).enumerate() {
+        let (x, y) = (i % out_width, i / out_width);
+        let src_x =
+            ((start_src_x + x as f64 * scale_factor) as usize).min(source_pattern.width - 1);
+        let src_y =
+            ((start_src_y + y as f64 * scale_factor) as usize).min(source_pattern.height - 1);
+
+        let marker = debug_border_color(
+            src_x,
+            src_y,
+            source_pattern.width,
+            source_pattern.height,
+            options.debug_border_inset,
+            corner_alpha,
+        );
+        if options.borders_only {
+            pixel.copy_from_slice(&[0, 0, 0, 0]);
+        }
+
+        // Source-over onto the premultiplied pixel
+        if let Some(color) = marker {
+            let alpha = color[3] as u32;
+            for channel in 0..4 {
+                let marker_value = if channel == 3 {
+                    255
+                } else {
+                    color[channel] as u32
+                };
+                pixel[channel] =
+                    ((marker_value * alpha + pixel[channel] as u32 * (255 - alpha) + 127) / 255)
+                        as u8;
             }
-            PatternType::Text => {
-                self.generate_text_pattern(&mut buffer, width, height, bytes_per_row, state)
+        }
+    }
+}
+
+// Draw rulers along the top and left edges labeled in source pixels, and
+// a scale bar one tick interval long in the bottom-left corner. Both are
+// drawn in viewport space after the filters, so they never get keyed out.
+fn apply_rulers(
+    pixels: &mut [u8],
+    out_width: usize,
+    out_height: usize,
+    zoom_level: f64,
+    view_x: f64,
+    view_y: f64,
+) {
+    const BACKGROUND: [u8; 3] = [235, 235, 235];
+    const INK: [u8; 3] = [40, 40, 40];
+
+    let bytes_per_row = out_width * 4;
+    let mut fill = |x0: usize, y0: usize, x1: usize, y1: usize, color: [u8; 3]| {
+        for y in y0..y1.min(out_height) {
+            for x in x0..x1.min(out_width) {
+                let idx = px(pixels, bytes_per_row, x, y);
+                pixels[idx..idx + 4].copy_from_slice(&[color[0], color[1], color[2], 255]);
             }
         }
+    };
+
+    let spacing = ruler_tick_spacing(zoom_level);
+    let minor_spacing = match spacing {
+        1 => 1,
+        spacing if spacing % 5 == 0 => spacing / 5,
+        spacing => spacing / 2,
+    };
+
+    // Same source origin as render_region; source coordinate t starts at
+    // viewport offset (t - start) * zoom
+    let scale_factor = 1.0 / zoom_level;
+    let start_src_x = (view_x * scale_factor).max(0.0);
+    let start_src_y = (view_y * scale_factor).max(0.0);
+    let offset = |t: usize, start: f64| ((t as f64 - start) * zoom_level).ceil() as usize;
+    let first_tick = |start: f64| (start.ceil() as usize).div_ceil(minor_spacing) * minor_spacing;
+
+    fill(0, 0, out_width, RULER_SIZE, BACKGROUND);
+    fill(0, 0, RULER_SIZE, out_height, BACKGROUND);
+
+    let mut labels = Vec::new();
+    let mut t = first_tick(start_src_x);
+    while offset(t, start_src_x) < out_width {
+        let x = offset(t, start_src_x);
+        if x >= RULER_SIZE {
+            let length = if t % spacing == 0 {
+                labels.push((t.to_string(), x + 2, 2));
+                RULER_SIZE
+            } else {
+                RULER_SIZE / 3
+            };
+            fill(x, RULER_SIZE - length, x + 1, RULER_SIZE, INK);
+        }
+        t += minor_spacing;
+    }
 
-        self.add_debug_borders(&mut buffer, width, height, bytes_per_row);
+    let mut t = first_tick(start_src_y);
+    while offset(t, start_src_y) < out_height {
+        let y = offset(t, start_src_y);
+        if y >= RULER_SIZE {
+            let length = if t % spacing == 0 {
+                // The left ruler is too narrow for a row of digits, so stack them
+                for (index, digit) in t.to_string().chars().enumerate() {
+                    labels.push((digit.to_string(), 2, y + 2 + index * 6));
+                }
+                RULER_SIZE
+            } else {
+                RULER_SIZE / 3
+            };
+            fill(RULER_SIZE - length, y, RULER_SIZE, y + 1, INK);
+        }
+        t += minor_spacing;
+    }
 
-        SourcePattern {
-            buffer,
-            width,
-            height,
+    // The scale bar, on a background patch so it reads over any content
+    let bar_length = (spacing as f64 * zoom_level).round() as usize;
+    let bar_x = RULER_SIZE + 8;
+    if out_height > RULER_SIZE + 24 && bar_x + bar_length + 4 < out_width {
+        let bar_y = out_height - 8;
+        fill(
+            bar_x - 4,
+            bar_y - 14,
+            bar_x + bar_length + 4,
+            bar_y + 5,
+            BACKGROUND,
+        );
+        fill(bar_x, bar_y - 1, bar_x + bar_length, bar_y + 2, INK);
+        fill(bar_x, bar_y - 4, bar_x + 1, bar_y + 2, INK);
+        fill(
+            bar_x + bar_length - 1,
+            bar_y - 4,
+            bar_x + bar_length,
+            bar_y + 2,
+            INK,
+        );
+        labels.push((spacing.to_string(), bar_x + 3, bar_y - 11));
+    }
+
+    let char_map: std::collections::HashMap<char, usize> = CHAR_INDICES.iter().cloned().collect();
+    for (text, x, y) in labels {
+        draw_text(
+            pixels,
+            out_width,
+            out_height,
             bytes_per_row,
+            &BITMAP_CHARS,
+            &char_map,
+            &text,
+            x,
+            y,
+            5,
+            5,
+            1,
+            INK,
+        );
+    }
+}
+
+// Apply color filters, in order, to an RGBA viewport buffer
+fn apply_color_filters(pixels: &mut [u8], filters: &[ColorFilter]) {
+    for filter in filters {
+        match *filter {
+            ColorFilter::ChromaKey { color, tolerance } => {
+                for pixel in pixels.chunks_exact_mut(4) {
+                    let matches_key =
+                        (0..3).all(|channel| pixel[channel].abs_diff(color[channel]) <= tolerance);
+
+                    // The bitmap is premultiplied, so clear color along with alpha
+                    if matches_key {
+                        pixel.copy_from_slice(&[0, 0, 0, 0]);
+                    }
+                }
+            }
+            ColorFilter::ColorBlind(kind) => {
+                for pixel in pixels.chunks_exact_mut(4) {
+                    let alpha = pixel[3] as u32;
+                    if alpha == 0 {
+                        continue;
+                    }
+
+                    // The simulation works on straight color, so undo the
+                    // premultiplication first and redo it afterwards
+                    let straight = [0, 1, 2].map(|channel| {
+                        ((pixel[channel] as u32 * 255 + alpha / 2) / alpha).min(255) as u8
+                    });
+                    let simulated = simulate_color_blindness(straight, kind);
+                    for channel in 0..3 {
+                        pixel[channel] = ((simulated[channel] as u32 * alpha + 127) / 255) as u8;
+                    }
+                }
+            }
         }
     }
+}
 
-    // Generate a checkerboard pattern
-    fn generate_checkerboard_pattern(
-        &self,
-        buffer: &mut Vec<u8>,
-        width: usize,
-        height: usize,
-        bytes_per_row: usize,
-    ) {
+// Alternating squares in two colors; colors[0] is the top-left square
+#[derive(Debug)]
+struct CheckerboardPattern {
+    colors: [[u8; 4]; 2],
+}
+
+impl PatternGenerator for CheckerboardPattern {
+    fn generate(&self, buffer: &mut [u8], width: usize, height: usize, bytes_per_row: usize) {
+        let colors = self.colors;
         let square_size = 20;
 
         for y in 0..height {
             for x in 0..width {
-                let idx = y * bytes_per_row + x * 4;
-                let is_white = ((x / square_size) + (y / square_size)) % 2 == 0;
-                let color = if is_white { 255u8 } else { 0u8 };
+                let idx = px(buffer, bytes_per_row, x, y);
+                let color = colors[((x / square_size) + (y / square_size)) % 2];
 
-                buffer[idx] = color;
-                buffer[idx + 1] = color;
-                buffer[idx + 2] = color;
-                buffer[idx + 3] = 255;
+                buffer[idx] = color[0];
+                buffer[idx + 1] = color[1];
+                buffer[idx + 2] = color[2];
+                buffer[idx + 3] = color[3];
             }
         }
     }
+}
 
-    // Generate a gradient pattern
-    fn generate_gradient_pattern(
-        &self,
-        buffer: &mut Vec<u8>,
-        width: usize,
-        height: usize,
-        bytes_per_row: usize,
-    ) {
+// Red increasing to the right and green increasing downwards over a blue base
+#[derive(Debug)]
+struct GradientPattern;
+
+impl PatternGenerator for GradientPattern {
+    fn generate(&self, buffer: &mut [u8], width: usize, height: usize, bytes_per_row: usize) {
         for y in 0..height {
             for x in 0..width {
-                let idx = y * bytes_per_row + x * 4;
+                let idx = px(buffer, bytes_per_row, x, y);
                 let r = ((x as f64) / (width as f64) * 255.0) as u8;
                 let g = ((y as f64) / (height as f64) * 255.0) as u8;
                 let b = 200u8;
@@ -1170,20 +6536,117 @@ impl AppDelegate {
             }
         }
     }
+}
+
+// Banner text in the bitmap font, with the file name and a "FILE SELECTED"
+// note underneath when a secondary line is set
+#[derive(Debug)]
+struct TextPattern<'a> {
+    primary_text: Option<&'a str>,
+    secondary_text: Option<&'a str>,
+    text_scale: f64,
+    direction: TextDirection,
+}
+
+impl TextPattern<'_> {
+    // Characters of a line in the order they appear from left to right
+    fn visual_order(&self, text: &str) -> String {
+        match self.direction {
+            TextDirection::RightToLeft => text.chars().rev().collect(),
+            _ => text.to_string(),
+        }
+    }
+
+    // The file name line, shortened to fit
+    fn secondary_display_text(&self) -> Option<String> {
+        self.secondary_text.map(|secondary_text| {
+            // Limit text length if needed, counting characters rather than
+            // bytes so multi-byte file names never split a code point
+            if secondary_text.chars().count() > 30 {
+                let truncated: String = secondary_text.chars().take(27).collect();
+                format!("{}...", truncated)
+            } else {
+                secondary_text.to_string()
+            }
+        })
+    }
 
-    // Generate a text pattern
-    fn generate_text_pattern(
+    // Vertical layout: each line becomes a column of glyphs, centered
+    // vertically. The banner is the middle column, the file name the next
+    // column to its left and the "FILE SELECTED" note sits near the left edge.
+    fn generate_vertical(
         &self,
-        buffer: &mut Vec<u8>,
+        buffer: &mut [u8],
         width: usize,
         height: usize,
         bytes_per_row: usize,
-        state: &AppState,
+        char_map: &std::collections::HashMap<char, usize>,
+        scale: f64,
     ) {
+        let primary = self.primary_text.unwrap_or("COMING SOON").to_string();
+        let secondary = self.secondary_display_text();
+        let gap = (40.0 * scale) as usize;
+
+        // (text, base glyph width/height/padding, color)
+        let mut columns = vec![(primary, (32, 40, 4), [30, 30, 180])];
+        if let Some(secondary) = secondary {
+            columns.push((secondary, (16, 20, 2), [20, 120, 20]));
+            columns.push(("FILE SELECTED".to_string(), (12, 15, 1), [150, 50, 50]));
+        }
+
+        let mut right_edge: Option<usize> = None;
+        for (index, (text, (base_width, base_height, base_padding), color)) in
+            columns.into_iter().enumerate()
+        {
+            let count = text.chars().count();
+
+            // Fit the column to the canvas height, the same way lines are fitted to its width
+            let (char_height, char_width, char_padding) = scaled_text_metrics(
+                (base_height, base_width, base_padding),
+                scale,
+                count,
+                height,
+            );
+
+            let x = match (index, right_edge) {
+                (0, _) | (_, None) => width.saturating_sub(char_width) / 2,
+                (2, _) => (20.0 * scale) as usize,
+                (_, Some(right_edge)) => right_edge.saturating_sub(gap + char_width),
+            };
+            let column_height = count * (char_height + char_padding);
+            let start_y = height.saturating_sub(column_height) / 2;
+
+            for (i, c) in text.chars().enumerate() {
+                draw_text(
+                    buffer,
+                    width,
+                    height,
+                    bytes_per_row,
+                    &BITMAP_CHARS,
+                    char_map,
+                    &c.to_string(),
+                    x,
+                    start_y + i * (char_height + char_padding),
+                    char_width,
+                    char_height,
+                    char_padding,
+                    color,
+                );
+            }
+
+            if index == 0 {
+                right_edge = Some(x);
+            }
+        }
+    }
+}
+
+impl PatternGenerator for TextPattern<'_> {
+    fn generate(&self, buffer: &mut [u8], width: usize, height: usize, bytes_per_row: usize) {
         // Fill with light blue-gray background
         for y in 0..height {
             for x in 0..width {
-                let idx = y * bytes_per_row + x * 4;
+                let idx = px(buffer, bytes_per_row, x, y);
                 buffer[idx] = 230;
                 buffer[idx + 1] = 235;
                 buffer[idx + 2] = 240;
@@ -1194,26 +6657,35 @@ impl AppDelegate {
         let char_map: std::collections::HashMap<char, usize> =
             CHAR_INDICES.iter().cloned().collect();
 
-        let primary = state.primary_text.as_deref().unwrap_or("COMING SOON");
+        let primary = self.primary_text.unwrap_or("COMING SOON");
+
+        // Text is laid out for an 800x600 canvas and scaled with the canvas,
+        // then by the user's text size preference
+        let scale = self.text_scale * (width as f64 / 800.0).min(height as f64 / 600.0);
+
+        if self.direction == TextDirection::TopToBottom {
+            self.generate_vertical(buffer, width, height, bytes_per_row, &char_map, scale);
+            return;
+        }
 
         // Text sizing and positioning
-        let char_width = 32;
-        let char_height = 40;
-        let char_padding = 4;
+        let (char_width, char_height, char_padding) =
+            scaled_text_metrics((32, 40, 4), scale, primary.chars().count(), width);
 
-        let text_width = primary.len() * (char_width + char_padding);
-        let start_x = (width - text_width) / 2;
-        let start_y = height / 2 - char_height;
+        // Positions saturate at the top-left edge if the text still doesn't fit
+        let text_width = primary.chars().count() * (char_width + char_padding);
+        let start_x = width.saturating_sub(text_width) / 2;
+        let start_y = (height / 2).saturating_sub(char_height);
 
         // Draw primary text
-        self.draw_text(
+        draw_text(
             buffer,
             width,
             height,
             bytes_per_row,
             &BITMAP_CHARS,
             &char_map,
-            primary,
+            &self.visual_order(primary),
             start_x,
             start_y,
             char_width,
@@ -1223,31 +6695,23 @@ impl AppDelegate {
         );
 
         // Draw secondary text if available
-        if let Some(secondary) = &state.secondary_text {
-            let secondary_text = secondary;
-            let smaller_char_width = 16;
-            let smaller_char_height = 20;
-            let smaller_padding = 2;
-
-            // Limit text length if needed
-            let display_text = if secondary_text.len() > 30 {
-                format!("{}...", &secondary_text[0..27])
-            } else {
-                secondary_text.to_string()
-            };
+        if let Some(display_text) = self.secondary_display_text() {
+            let (smaller_char_width, smaller_char_height, smaller_padding) =
+                scaled_text_metrics((16, 20, 2), scale, display_text.chars().count(), width);
 
-            let secondary_text_width = display_text.len() * (smaller_char_width + smaller_padding);
-            let secondary_x = (width - secondary_text_width) / 2;
-            let secondary_y = start_y + char_height + 40; // Below primary text
+            let secondary_text_width =
+                display_text.chars().count() * (smaller_char_width + smaller_padding);
+            let secondary_x = width.saturating_sub(secondary_text_width) / 2;
+            let secondary_y = start_y + char_height + (40.0 * scale) as usize; // Below primary text
 
-            self.draw_text(
+            draw_text(
                 buffer,
                 width,
                 height,
                 bytes_per_row,
                 &BITMAP_CHARS,
                 &char_map,
-                &display_text.to_uppercase(),
+                &self.visual_order(&display_text),
                 secondary_x,
                 secondary_y,
                 smaller_char_width,
@@ -1258,24 +6722,23 @@ impl AppDelegate {
         }
 
         // Add "FILE SELECTED" text if there's a secondary text
-        if state.secondary_text.is_some() {
+        if self.secondary_text.is_some() {
             let info_text = "FILE SELECTED";
-            let small_char_width = 12;
-            let small_char_height = 15;
-            let small_padding = 1;
+            let (small_char_width, small_char_height, small_padding) =
+                scaled_text_metrics((12, 15, 1), scale, info_text.chars().count(), width);
 
-            let info_text_width = info_text.len() * (small_char_width + small_padding);
-            let info_x = (width - info_text_width) / 2;
-            let info_y = height - 60; // Near bottom
+            let info_text_width = info_text.chars().count() * (small_char_width + small_padding);
+            let info_x = width.saturating_sub(info_text_width) / 2;
+            let info_y = height.saturating_sub((60.0 * scale) as usize); // Near bottom
 
-            self.draw_text(
+            draw_text(
                 buffer,
                 width,
                 height,
                 bytes_per_row,
                 &BITMAP_CHARS,
                 &char_map,
-                info_text,
+                &self.visual_order(info_text),
                 info_x,
                 info_y,
                 small_char_width,
@@ -1285,187 +6748,636 @@ impl AppDelegate {
             );
         }
     }
+}
 
-    // Helper to draw text with the bitmap font
-    fn draw_text(
-        &self,
-        buffer: &mut Vec<u8>,
-        width: usize,
-        height: usize,
-        bytes_per_row: usize,
-        characters: &[[[u8; 5]; 5]],
-        char_map: &std::collections::HashMap<char, usize>,
-        text: &str,
-        start_x: usize,
-        start_y: usize,
-        char_width: usize,
-        char_height: usize,
-        char_padding: usize,
-        color: [u8; 3],
-    ) {
-        // Scale factors to expand the 5x5 bitmap
-        let scale_x = char_width / 5;
-        let scale_y = char_height / 5;
-
-        for (i, c) in text.chars().enumerate() {
-            let char_idx = char_map.get(&c).copied().unwrap_or(10); // Default to space
-            let bitmap = &characters[char_idx];
-            let char_x = start_x + i * (char_width + char_padding);
-
-            for (y_idx, row) in bitmap.iter().enumerate() {
-                for (x_idx, &pixel) in row.iter().enumerate() {
-                    if pixel == 1 {
-                        for sy in 0..scale_y {
-                            for sx in 0..scale_x {
-                                let x = char_x + x_idx * scale_x + sx;
-                                let y = start_y + y_idx * scale_y + sy;
-
-                                if x >= width || y >= height {
-                                    continue;
-                                }
-
-                                let idx = y * bytes_per_row + x * 4;
-                                if idx + 3 < buffer.len() {
-                                    buffer[idx] = color[0];
-                                    buffer[idx + 1] = color[1];
-                                    buffer[idx + 2] = color[2];
-                                    buffer[idx + 3] = 255;
-                                }
+// Helper to draw text with the bitmap font
+fn draw_text(
+    buffer: &mut [u8],
+    width: usize,
+    height: usize,
+    bytes_per_row: usize,
+    characters: &[[[u8; 5]; 5]],
+    char_map: &std::collections::HashMap<char, usize>,
+    text: &str,
+    start_x: usize,
+    start_y: usize,
+    char_width: usize,
+    char_height: usize,
+    char_padding: usize,
+    color: [u8; 3],
+) {
+    // Scale factors to expand the 5x5 bitmap
+    let scale_x = char_width / 5;
+    let scale_y = char_height / 5;
+
+    for (i, c) in text.chars().enumerate() {
+        let char_idx = char_map.get(&c).copied().unwrap_or(MISSING_GLYPH);
+        let bitmap = &characters[char_idx];
+        let char_x = start_x + i * (char_width + char_padding);
+
+        for (y_idx, row) in bitmap.iter().enumerate() {
+            for (x_idx, &pixel) in row.iter().enumerate() {
+                if pixel == 1 {
+                    for sy in 0..scale_y {
+                        for sx in 0..scale_x {
+                            let x = char_x + x_idx * scale_x + sx;
+                            let y = start_y + y_idx * scale_y + sy;
+
+                            if x >= width || y >= height {
+                                continue;
                             }
+
+                            let idx = px(buffer, bytes_per_row, x, y);
+                            buffer[idx] = color[0];
+                            buffer[idx + 1] = color[1];
+                            buffer[idx + 2] = color[2];
+                            buffer[idx + 3] = 255;
                         }
                     }
                 }
             }
         }
     }
+}
+
+// Scale a (glyph width, glyph height, padding) triple for the text pattern,
+// shrinking it further if a line of `chars` glyphs would be wider than
+// `max_width`. Glyphs never go below the 5x5 size of the bitmap font.
+fn scaled_text_metrics(
+    base: (usize, usize, usize),
+    scale: f64,
+    chars: usize,
+    max_width: usize,
+) -> (usize, usize, usize) {
+    let (base_width, base_height, base_padding) = base;
+    let line_width = (chars * (base_width + base_padding)) as f64 * scale;
+
+    let scale = if line_width > max_width as f64 {
+        scale * max_width as f64 / line_width
+    } else {
+        scale
+    };
+
+    (
+        ((base_width as f64 * scale) as usize).max(5),
+        ((base_height as f64 * scale) as usize).max(5),
+        (base_padding as f64 * scale) as usize,
+    )
+}
+
+fn srgb_to_linear(value: u8) -> f64 {
+    let value = value as f64 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
 
-    // Add debug borders and corner markers to the source pattern
-    fn add_debug_borders(
-        &self,
-        buffer: &mut Vec<u8>,
-        width: usize,
-        height: usize,
-        bytes_per_row: usize,
-    ) {
-        let border_thickness = 3;
-        let corner_size = 15;
-
-        // Color definitions for borders and corner markers
-        let red = [255u8, 0, 0, 255];
-        let green = [0u8, 255, 0, 255];
-        let blue = [0u8, 0, 255, 255];
-        let yellow = [255u8, 255, 0, 255];
-
-        // Draw top and bottom borders
-        for y in 0..border_thickness {
-            // Top edge
-            for x in 0..width {
-                let idx = y * bytes_per_row + x * 4;
-                buffer[idx] = red[0];
-                buffer[idx + 1] = red[1];
-                buffer[idx + 2] = red[2];
-                buffer[idx + 3] = red[3];
-            }
-
-            // Bottom edge
-            if height > border_thickness {
-                for x in 0..width {
-                    let idx = (height - 1 - y) * bytes_per_row + x * 4;
-                    buffer[idx] = red[0];
-                    buffer[idx + 1] = red[1];
-                    buffer[idx + 2] = red[2];
-                    buffer[idx + 3] = red[3];
-                }
-            }
+fn linear_to_srgb(value: f64) -> u8 {
+    let value = value.clamp(0.0, 1.0);
+    let encoded = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    (encoded * 255.0).round() as u8
+}
+
+// An sRGB color as it appears with the given color blindness, computed in
+// linear light
+fn simulate_color_blindness(rgb: [u8; 3], kind: ColorBlindType) -> [u8; 3] {
+    let linear = rgb.map(srgb_to_linear);
+    kind.matrix()
+        .map(|row| linear_to_srgb(row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2]))
+}
+
+// Largest and mean absolute difference between corresponding channel values
+// of two equally sized pixel buffers
+fn pixel_difference(a: &[u8], b: &[u8]) -> (u8, f64) {
+    debug_assert_eq!(a.len(), b.len());
+    if a.is_empty() {
+        return (0, 0.0);
+    }
+
+    let (max, total) = a.iter().zip(b).fold((0u8, 0u64), |(max, total), (&a, &b)| {
+        let difference = a.abs_diff(b);
+        (max.max(difference), total + difference as u64)
+    });
+    (max, total as f64 / a.len() as f64)
+}
+
+// Source pixels between labeled ruler ticks: the smallest 1-2-5 step that
+// keeps ticks at least RULER_MIN_TICK_SPACING viewport pixels apart
+fn ruler_tick_spacing(zoom_level: f64) -> usize {
+    (0..10)
+        .map(|exponent| 10usize.pow(exponent))
+        .flat_map(|magnitude| [magnitude, 2 * magnitude, 5 * magnitude])
+        .find(|&spacing| spacing as f64 * zoom_level >= RULER_MIN_TICK_SPACING)
+        .unwrap_or(1)
+}
+
+// Byte offset of pixel (x, y) in a tightly packed RGBA buffer. Debug builds
+// assert the whole pixel is in bounds, so an indexing mistake in a pattern
+// generator fails at the faulty write rather than corrupting a neighbour.
+fn px(buf: &[u8], bytes_per_row: usize, x: usize, y: usize) -> usize {
+    let idx = y * bytes_per_row + x * 4;
+    debug_assert!(
+        x * 4 + 3 < bytes_per_row && idx + 3 < buf.len(),
+        "pixel ({}, {}) out of bounds for {} bytes at {} bytes per row",
+        x,
+        y,
+        buf.len(),
+        bytes_per_row
+    );
+    idx
+}
+
+// Color of the debug marker covering a source pixel, if any: a red stroke
+// around the edges and a box in each corner (red top-left, green top-right,
+// blue bottom-left, yellow bottom-right), all drawn `inset` pixels in from the
+// source edges. Corner boxes only appear when there's room for them, and carry
+// `corner_alpha` as their alpha so they can be blended over the content.
+fn debug_border_color(
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    inset: usize,
+    corner_alpha: u8,
+) -> Option<[u8; 4]> {
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    let red = [255, 0, 0, corner_alpha];
+    let green = [0, 255, 0, corner_alpha];
+    let blue = [0, 0, 255, corner_alpha];
+    let yellow = [255, 255, 0, corner_alpha];
+
+    // Work in the coordinates of the inset frame
+    let frame_width = width.saturating_sub(2 * inset);
+    let frame_height = height.saturating_sub(2 * inset);
+    let (x, y) = (x.checked_sub(inset)?, y.checked_sub(inset)?);
+    if x >= frame_width || y >= frame_height {
+        return None;
+    }
+
+    let left = x < DEBUG_CORNER_SIZE;
+    let top = y < DEBUG_CORNER_SIZE;
+    let right = frame_width > DEBUG_CORNER_SIZE && x >= frame_width - DEBUG_CORNER_SIZE;
+    let bottom = frame_height > DEBUG_CORNER_SIZE && y >= frame_height - DEBUG_CORNER_SIZE;
+
+    let on_stroke = x < DEBUG_BORDER_THICKNESS
+        || y < DEBUG_BORDER_THICKNESS
+        || x >= frame_width.saturating_sub(DEBUG_BORDER_THICKNESS)
+        || y >= frame_height.saturating_sub(DEBUG_BORDER_THICKNESS);
+
+    if right && bottom {
+        Some(yellow)
+    } else if left && bottom {
+        Some(blue)
+    } else if right && top {
+        Some(green)
+    } else if left && top {
+        Some(red)
+    } else if on_stroke {
+        Some(RED)
+    } else {
+        None
+    }
+}
+
+// Frame of the image area above the control bar. The height is clamped so a
+// window shorter than the controls never yields a negative-sized frame.
+fn main_view_frame(content_frame: NSRect, controls_height: f64) -> NSRect {
+    debug_assert!(controls_height >= 0.0);
+
+    NSRect::new(
+        NSPoint::new(0.0, controls_height),
+        NSSize::new(
+            content_frame.size.width.max(0.0),
+            (content_frame.size.height - controls_height).max(0.0),
+        ),
+    )
+}
+
+// Allocate an 8-bit RGBA bitmap with tightly packed rows, owning its pixel buffer
+fn new_rgba_bitmap(width: usize, height: usize) -> Retained<NSBitmapImageRep> {
+    let alloc = NSBitmapImageRep::alloc();
+    let color_space_name = ns_string!("NSDeviceRGBColorSpace");
+    let bits_per_component = 8;
+    let bytes_per_row = width * 4;
+
+    unsafe {
+        let planes: *const *mut u8 = std::ptr::null();
+        let rep: Retained<NSBitmapImageRep> = msg_send![alloc,
+            initWithBitmapDataPlanes: planes,
+            pixelsWide: width as isize,
+            pixelsHigh: height as isize,
+            bitsPerSample: bits_per_component as isize,
+            samplesPerPixel: 4 as isize,
+            hasAlpha: true,
+            isPlanar: false,
+            colorSpaceName: &*color_space_name,
+            bytesPerRow: bytes_per_row as isize,
+            bitsPerPixel: 32 as isize
+        ];
+
+        rep
+    }
+}
+
+// Encode a tightly packed RGBA buffer as PNG data
+fn rgba_to_png(pixels: &[u8], width: usize, height: usize) -> Option<Retained<NSData>> {
+    let rep = new_rgba_bitmap(width, height);
+    let buffer: *mut u8 = unsafe { msg_send![&*rep, bitmapData] };
+    if buffer.is_null() || pixels.len() != width * height * 4 {
+        return None;
+    }
+
+    unsafe {
+        std::ptr::copy_nonoverlapping(pixels.as_ptr(), buffer, pixels.len());
+        rep.representationUsingType_properties(NSBitmapImageFileType::PNG, &NSDictionary::new())
+    }
+}
+
+// Timestamped file name for quick screenshots, e.g. cocoa-renderer-20250101-120000.png
+fn screenshot_file_name() -> String {
+    unsafe {
+        // A fixed format needs the POSIX locale, or the user's calendar and
+        // digits leak into the name
+        let formatter = NSDateFormatter::new();
+        let locale = NSLocale::localeWithLocaleIdentifier(ns_string!("en_US_POSIX"));
+        formatter.setLocale(Some(&locale));
+        formatter.setDateFormat(Some(ns_string!("yyyyMMdd-HHmmss")));
+        let timestamp = formatter.stringFromDate(&NSDate::now());
+        format!("cocoa-renderer-{}.png", timestamp)
+    }
+}
+
+// Format RGBA components as an RRGGBBAA hex string
+fn rgba_to_hex(rgba: [u8; 4]) -> String {
+    format!(
+        "{:02X}{:02X}{:02X}{:02X}",
+        rgba[0], rgba[1], rgba[2], rgba[3]
+    )
+}
+
+// Parse an RRGGBBAA hex string into RGBA components
+fn hex_to_rgba(hex: &str) -> Option<[u8; 4]> {
+    if hex.len() != 8 {
+        return None;
+    }
+
+    u32::from_str_radix(hex, 16).ok().map(u32::to_be_bytes)
+}
+
+// Convert an NSColor to 8-bit RGBA components in the device RGB color space
+fn ns_color_to_rgba(color: &NSColor) -> Option<[u8; 4]> {
+    let rgb = unsafe { color.colorUsingColorSpace(&NSColorSpace::deviceRGBColorSpace()) }?;
+
+    let to_byte = |component: f64| (component.clamp(0.0, 1.0) * 255.0).round() as u8;
+    unsafe {
+        Some([
+            to_byte(rgb.redComponent()),
+            to_byte(rgb.greenComponent()),
+            to_byte(rgb.blueComponent()),
+            to_byte(rgb.alphaComponent()),
+        ])
+    }
+}
+
+// Convert 8-bit RGBA components to an NSColor in the device RGB color space
+fn rgba_to_ns_color(rgba: [u8; 4]) -> Retained<NSColor> {
+    unsafe {
+        NSColor::colorWithDeviceRed_green_blue_alpha(
+            rgba[0] as f64 / 255.0,
+            rgba[1] as f64 / 255.0,
+            rgba[2] as f64 / 255.0,
+            rgba[3] as f64 / 255.0,
+        )
+    }
+}
+
+// Single-color pattern, registered by the self-test and the unit tests to
+// exercise the custom generator path
+#[derive(Debug)]
+struct SolidPattern([u8; 4]);
+
+impl PatternGenerator for SolidPattern {
+    fn generate(&self, buffer: &mut [u8], _width: usize, _height: usize, _bytes_per_row: usize) {
+        for pixel in buffer.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&self.0);
         }
+    }
+}
+
+const GOLDEN_FILE_NAME: &str = "golden-2.jp2";
+
+// A configuration timed by the render benchmark. Text cases use
+// GOLDEN_FILE_NAME as the secondary text, and view offsets are always 0.
+struct BenchRender {
+    name: &'static str,
+    pattern_type: PatternType,
+    size: (usize, usize),
+    zoom_level: f64,
+    sampling: SamplingMode,
+}
+
+// Fixed so reports from different builds are comparable; add new cases at
+// the end rather than changing existing ones
+const BENCH_RENDERS: [BenchRender; 6] = [
+    BenchRender {
+        name: "checkerboard-800x600-1x",
+        pattern_type: PatternType::Checkerboard,
+        size: (800, 600),
+        zoom_level: 1.0,
+        sampling: SamplingMode::Nearest,
+    },
+    BenchRender {
+        name: "gradient-1920x1080-0.5x",
+        pattern_type: PatternType::Gradient,
+        size: (1920, 1080),
+        zoom_level: 0.5,
+        sampling: SamplingMode::Nearest,
+    },
+    BenchRender {
+        name: "gradient-1920x1080-0.5x-supersampled",
+        pattern_type: PatternType::Gradient,
+        size: (1920, 1080),
+        zoom_level: 0.5,
+        sampling: SamplingMode::Supersampled,
+    },
+    BenchRender {
+        name: "text-800x600-2.5x",
+        pattern_type: PatternType::Text,
+        size: (800, 600),
+        zoom_level: 2.5,
+        sampling: SamplingMode::Nearest,
+    },
+    BenchRender {
+        name: "text-1920x1080-1x",
+        pattern_type: PatternType::Text,
+        size: (1920, 1080),
+        zoom_level: 1.0,
+        sampling: SamplingMode::Nearest,
+    },
+    BenchRender {
+        name: "checkerboard-3840x2160-0.25x-supersampled",
+        pattern_type: PatternType::Checkerboard,
+        size: (3840, 2160),
+        zoom_level: 0.25,
+        sampling: SamplingMode::Supersampled,
+    },
+];
+
+const BENCH_DEFAULT_ITERATIONS: usize = 20;
+
+// Minimum, median and maximum of a set of timings
+fn timing_stats(samples: &mut [f64]) -> (f64, f64, f64) {
+    if samples.is_empty() {
+        return (0.0, 0.0, 0.0);
+    }
+
+    samples.sort_by(f64::total_cmp);
+    let middle = samples.len() / 2;
+    let median = if samples.len().is_multiple_of(2) {
+        (samples[middle - 1] + samples[middle]) / 2.0
+    } else {
+        samples[middle]
+    };
 
-        // Draw left and right borders
-        for x in 0..border_thickness {
-            // Left edge
-            for y in 0..height {
-                let idx = y * bytes_per_row + x * 4;
-                buffer[idx] = red[0];
-                buffer[idx + 1] = red[1];
-                buffer[idx + 2] = red[2];
-                buffer[idx + 3] = red[3];
+    (samples[0], median, samples[samples.len() - 1])
+}
+
+// Source sizes, zoom levels and pans the smoke tests cover. Sizes include
+// degenerate and non-square ones, and stay small enough that the largest
+// output (80x60 at 10x) is under 2 MB.
+const SELFTEST_SIZES: [(usize, usize); 5] = [(1, 1), (3, 3), (2, 7), (17, 5), (80, 60)];
+const SELFTEST_ZOOM_LEVELS: [f64; 5] = [0.1, 0.5, 1.0, 2.5, 10.0];
+const SELFTEST_PANS: [(f64, f64); 4] =
+    [(0.0, 0.0), (13.5, -7.25), (-1000.0, 1000.0), (1.0e6, 1.0e6)];
+
+// Smoke test: generate every pattern, including one registered with the
+// delegate, at each SELFTEST_SIZES size and render it through the full
+// viewport pipeline at every zoom/pan combination, reporting any panics.
+// Each size is also wrapped in an NSImage and encoded as PNG, which unit
+// tests can't do because they need AppKit on the main thread. Returns the
+// number of failed cases.
+fn run_selftest(mtm: MainThreadMarker) -> usize {
+    let delegate = AppDelegate::new(mtm);
+    let custom = delegate.register_pattern_generator(Rc::new(SolidPattern([40, 80, 120, 255])));
+
+    let mut failures = 0;
+    let mut cases = 0;
+
+    for pattern_type in PatternType::ALL.into_iter().chain([custom]) {
+        for (width, height) in SELFTEST_SIZES {
+            let state = AppState {
+                pattern_type: pattern_type.clone(),
+                source_width: width,
+                source_height: height,
+                chroma_key_enabled: true,
+                ..AppState::default()
+            };
+            let (options, filters) = (state.render_options(), state.active_filters());
+
+            cases += 1;
+            let generated = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                delegate.generate_source_pattern_from_state(&state)
+            }));
+            let Ok(pattern) = generated else {
+                println!(
+                    "SELFTEST FAIL: generate {:?} {}x{}",
+                    pattern_type, width, height
+                );
+                failures += 1;
+                continue;
+            };
+
+            cases += 1;
+            let pixels = render_pixels(&pattern, 1.0, 0.0, 0.0, (width, height), options, &filters);
+            let image = delegate.generate_viewport_image(&pixels, width, height, 1.0);
+            if image.is_none() || rgba_to_png(&pixels, width, height).is_none() {
+                println!(
+                    "SELFTEST FAIL: image {:?} {}x{}",
+                    pattern_type, width, height
+                );
+                failures += 1;
             }
 
-            // Right edge
-            if width > border_thickness {
-                for y in 0..height {
-                    let idx = y * bytes_per_row + (width - 1 - x) * 4;
-                    buffer[idx] = red[0];
-                    buffer[idx + 1] = red[1];
-                    buffer[idx + 2] = red[2];
-                    buffer[idx + 3] = red[3];
+            for zoom_level in SELFTEST_ZOOM_LEVELS {
+                for (view_x, view_y) in SELFTEST_PANS {
+                    cases += 1;
+                    let rendered = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                        let size = scaled_source_size(&pattern, zoom_level);
+                        render_pixels(
+                            &pattern, zoom_level, view_x, view_y, size, options, &filters,
+                        )
+                    }));
+                    if rendered.is_err() {
+                        println!(
+                            "SELFTEST FAIL: render {:?} {}x{} zoom={} pan=({}, {})",
+                            pattern_type, width, height, zoom_level, view_x, view_y
+                        );
+                        failures += 1;
+                    }
                 }
             }
         }
+    }
 
-        // Draw colored corner boxes
-        self.draw_corner_box(buffer, bytes_per_row, 0, 0, corner_size, red);
+    println!("SELFTEST: {} of {} cases failed", failures, cases);
+    failures
+}
 
-        if width > corner_size {
-            self.draw_corner_box(
-                buffer,
-                bytes_per_row,
-                width - corner_size,
-                0,
-                corner_size,
-                green,
-            );
-        }
+// Export a zoom animation as numbered PNG frames, for demo GIFs and docs:
+//
+//   jp2view --export-zoom-sequence <start> <end> <frames> <dir> [pattern]
+//
+// Zoom is interpolated geometrically so the motion looks uniform. Every frame
+// has the source's dimensions and stays centered on the source's center;
+// zooming out past 1.0 letterboxes it on the view background. The pattern
+// is checkerboard, gradient or text (the default), rendered with the saved
+// sampling mode, overlays and filters.
+fn run_zoom_sequence_export(mtm: MainThreadMarker, args: &[String]) -> Result<usize, String> {
+    let usage = "usage: --export-zoom-sequence <start> <end> <frames> <dir> [pattern]";
+    let number = |index: usize| -> Result<f64, String> {
+        args.get(index)
+            .and_then(|arg| arg.parse::<f64>().ok())
+            .filter(|value| value.is_finite() && *value > 0.0)
+            .ok_or_else(|| usage.to_string())
+    };
+
+    let start_zoom = number(0)?;
+    let end_zoom = number(1)?;
+    let frames = args
+        .get(2)
+        .and_then(|arg| arg.parse::<usize>().ok())
+        .filter(|frames| *frames > 0)
+        .ok_or_else(|| usage.to_string())?;
+    let dir = Path::new(args.get(3).ok_or_else(|| usage.to_string())?);
+    let pattern_type = match args.get(4).map(String::as_str) {
+        None | Some("text") => PatternType::Text,
+        Some("checkerboard") => PatternType::Checkerboard,
+        Some("gradient") => PatternType::Gradient,
+        Some(other) => return Err(format!("unknown pattern {:?}", other)),
+    };
+
+    std::fs::create_dir_all(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
 
-        if height > corner_size {
-            self.draw_corner_box(
-                buffer,
-                bytes_per_row,
-                0,
-                height - corner_size,
-                corner_size,
-                blue,
-            );
-        }
+    let delegate = AppDelegate::new(mtm);
+    delegate.load_preferences();
+    let state = {
+        let mut state = delegate.ivars().state.borrow_mut();
+        state.pattern_type = pattern_type;
+        state.clone()
+    };
+    let pattern = delegate.generate_source_pattern_from_state(&state);
+    let (width, height) = (pattern.width, pattern.height);
+
+    for frame in 0..frames {
+        let t = if frames > 1 {
+            frame as f64 / (frames - 1) as f64
+        } else {
+            0.0
+        };
+        let zoom = start_zoom * (end_zoom / start_zoom).powf(t);
+
+        // Keep the source center on the frame center: pan into the zoomed
+        // source when it's larger than the frame, letterbox it when smaller.
+        // Returns the view offset, the inset in the frame and the rendered span.
+        let center = |length: usize| {
+            let zoomed = length as f64 * zoom;
+            if zoomed >= length as f64 {
+                ((zoomed - length as f64) / 2.0, 0, length)
+            } else {
+                let span = (zoomed.round() as usize).max(1);
+                (0.0, (length - span) / 2, span)
+            }
+        };
+        let (view_x, inset_x, span_x) = center(width);
+        let (view_y, inset_y, span_y) = center(height);
+
+        let region = render_pixels(
+            &pattern,
+            zoom,
+            view_x,
+            view_y,
+            (span_x, span_y),
+            state.render_options(),
+            &state.active_filters(),
+        );
 
-        if width > corner_size && height > corner_size {
-            self.draw_corner_box(
-                buffer,
-                bytes_per_row,
-                width - corner_size,
-                height - corner_size,
-                corner_size,
-                yellow,
-            );
+        let mut pixels = state.view_background.rgba().repeat(width * height);
+        for (row, line) in region.chunks_exact(span_x * 4).enumerate() {
+            let start = ((inset_y + row) * width + inset_x) * 4;
+            pixels[start..start + line.len()].copy_from_slice(line);
         }
-    }
 
-    fn draw_corner_box(
-        &self,
-        buffer: &mut Vec<u8>,
-        bytes_per_row: usize,
-        start_x: usize,
-        start_y: usize,
-        size: usize,
-        color: [u8; 4],
-    ) {
-        for y in 0..size {
-            for x in 0..size {
-                let idx = (start_y + y) * bytes_per_row + (start_x + x) * 4;
-                if idx + 3 < buffer.len() {
-                    buffer[idx] = color[0];
-                    buffer[idx + 1] = color[1];
-                    buffer[idx + 2] = color[2];
-                    buffer[idx + 3] = color[3];
-                }
-            }
-        }
+        let path = dir.join(format!("frame-{:04}.png", frame + 1));
+        let png = rgba_to_png(&pixels, width, height)
+            .ok_or_else(|| format!("failed to encode frame {}", frame + 1))?;
+        std::fs::write(&path, png.to_vec())
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+        println!("EXPORT: {} (zoom {:.3})", path.display(), zoom);
     }
+
+    Ok(frames)
 }
 
 fn main() {
     // Initialize on the main thread
     let mtm = MainThreadMarker::new().expect("Not running on main thread");
 
+    // Developer export: write a zoom animation as PNG frames and exit
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(index) = args.iter().position(|arg| arg == "--export-zoom-sequence") {
+        match run_zoom_sequence_export(mtm, &args[index + 1..]) {
+            Ok(frames) => {
+                println!("EXPORT: wrote {} frames", frames);
+                std::process::exit(0);
+            }
+            Err(message) => {
+                eprintln!("{}", message);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    // Developer benchmark: time the fixed render cases and write a JSON report
+    //
+    //   jp2view --bench-render [iterations] [report.json]
+    if let Some(index) = args.iter().position(|arg| arg == "--bench-render") {
+        let iterations = match args.get(index + 1).map(|arg| arg.parse::<usize>()) {
+            None => BENCH_DEFAULT_ITERATIONS,
+            Some(Ok(iterations)) if iterations > 0 => iterations,
+            Some(_) => {
+                eprintln!("usage: --bench-render [iterations] [report.json]");
+                std::process::exit(2);
+            }
+        };
+        let path = args
+            .get(index + 2)
+            .map_or("render-stats.json", String::as_str);
+
+        let report = render_benchmark_report(iterations);
+        match std::fs::write(path, report) {
+            Ok(()) => {
+                println!("BENCH: wrote {}", path);
+                std::process::exit(0);
+            }
+            Err(err) => {
+                eprintln!("{}: {}", path, err);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    // Hidden developer flag: run the rendering smoke test and exit
+    if std::env::args().any(|arg| arg == "--selftest") {
+        let failures = run_selftest(mtm);
+        std::process::exit(if failures == 0 { 0 } else { 1 });
+    }
+
     // Get the shared application instance
     let app = NSApplication::sharedApplication(mtm);
 
@@ -1484,3 +7396,690 @@ fn main() {
     println!("DEBUG: Starting application run loop");
     app.run();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The last name is all two-byte characters, so a byte-based cut at 27
+    // would land inside a code point
+    const SECONDARY_TEXTS: [Option<&str>; 3] = [
+        None,
+        Some("a-very-long-file-name-with-accents-éèê.jp2"),
+        Some("åäöåäöåäöåäöåäöåäöåäöåäöåäöåäö-scan.jp2"),
+    ];
+
+    #[test]
+    fn secondary_text_is_truncated_on_char_boundaries() {
+        for secondary_text in SECONDARY_TEXTS {
+            let text_pattern = TextPattern {
+                primary_text: None,
+                secondary_text,
+                text_scale: 1.0,
+                direction: TextDirection::LeftToRight,
+            };
+            if let Some(text) = text_pattern.secondary_display_text() {
+                assert!(text.chars().count() <= 30, "not truncated: {:?}", text);
+            }
+        }
+    }
+
+    // Glyphs are laid out per character, so a name with two-byte characters
+    // starts at the same place as an ASCII name of the same length
+    #[test]
+    fn secondary_text_layout_counts_characters() {
+        let leftmost_green = |secondary_text| {
+            let (width, height) = (800, 600);
+            let mut buffer = vec![0; width * height * 4];
+            TextPattern {
+                primary_text: None,
+                secondary_text: Some(secondary_text),
+                text_scale: 1.0,
+                direction: TextDirection::LeftToRight,
+            }
+            .generate(&mut buffer, width, height, width * 4);
+            buffer
+                .chunks_exact(4)
+                .enumerate()
+                .filter(|(_, pixel)| pixel[..3] == [20, 120, 20])
+                .map(|(index, _)| index % width)
+                .min()
+        };
+
+        assert!(leftmost_green("scan.jp2").is_some());
+        assert_eq!(leftmost_green("scän.jp2"), leftmost_green("scan.jp2"));
+    }
+
+    // None of the golden texts contain an 'x', so check its glyph directly
+    #[test]
+    fn x_glyph_is_a_diagonal_cross() {
+        let (_, index) = CHAR_INDICES.iter().find(|(c, _)| *c == 'x').unwrap();
+        let glyph = BITMAP_CHARS[*index];
+        assert_eq!(
+            glyph[1..4],
+            [[0, 1, 0, 1, 0], [0, 0, 1, 0, 0], [0, 1, 0, 1, 0]]
+        );
+        for (row, mirrored) in glyph.iter().zip(glyph.iter().rev()) {
+            assert_eq!(row, mirrored);
+        }
+    }
+
+    #[test]
+    fn color_blind_simulation_matches_reference() {
+        // Simulated colors match the reference matrices, and grays are unchanged
+        let checks = [
+            (ColorBlindType::Protanopia, [255, 0, 0], [109, 95, 0]),
+            (ColorBlindType::Deuteranopia, [255, 0, 0], [163, 144, 0]),
+            (ColorBlindType::Tritanopia, [0, 0, 255], [0, 107, 150]),
+            (ColorBlindType::Protanopia, [255, 255, 255], [255, 255, 255]),
+            (ColorBlindType::Tritanopia, [128, 128, 128], [128, 128, 128]),
+        ];
+        for (kind, input, expected) in checks {
+            let simulated = simulate_color_blindness(input, kind);
+            assert!(
+                (0..3).all(|channel| simulated[channel].abs_diff(expected[channel]) <= 1),
+                "{:?} of {:?} gave {:?}, expected {:?}",
+                kind,
+                input,
+                simulated,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn corrupt_jp2_data_is_an_error() {
+        let jp2_header = [
+            0x00, 0x00, 0x00, 0x0C, 0x6A, 0x50, 0x20, 0x20, 0x0D, 0x0A, 0x87, 0x0A,
+        ];
+        for bytes in [&b""[..], &b"not an image"[..], &jp2_header[..]] {
+            assert!(
+                matches!(
+                    SourcePattern::from_jp2_bytes(bytes),
+                    Err(DecodeError::Corrupt(_))
+                ),
+                "decoding {} bytes of bad JP2 data",
+                bytes.len()
+            );
+        }
+    }
+
+    // Decode a one-pixel image with the given 8-bit component samples
+    fn decode_jp2_pixel(
+        color_space: openjp2::openjpeg::OPJ_COLOR_SPACE,
+        samples: &[i32],
+    ) -> Result<[u8; 4], DecodeError> {
+        let mut samples = samples.to_vec();
+        let mut components: Vec<_> = samples
+            .iter_mut()
+            .map(|sample| openjp2::openjpeg::opj_image_comp {
+                dx: 1,
+                dy: 1,
+                w: 1,
+                h: 1,
+                x0: 0,
+                y0: 0,
+                prec: 8,
+                bpp: 8,
+                sgnd: 0,
+                resno_decoded: 0,
+                factor: 0,
+                data: sample,
+                alpha: 0,
+            })
+            .collect();
+        let image = openjp2::openjpeg::opj_image_t {
+            x0: 0,
+            y0: 0,
+            x1: 1,
+            y1: 1,
+            numcomps: components.len() as u32,
+            color_space,
+            comps: components.as_mut_ptr(),
+            icc_profile_buf: std::ptr::null_mut(),
+            icc_profile_len: 0,
+        };
+        let pattern = jp2_image_to_pattern(&image)?;
+        Ok(pattern.buffer[..4].try_into().unwrap())
+    }
+
+    #[test]
+    fn sycc_jp2_is_converted_to_rgb() {
+        use openjp2::openjpeg::COLOR_SPACE::OPJ_CLRSPC_SYCC;
+
+        // Neutral chroma is gray; pure red in sYCC is (76, 85, 255)
+        for (samples, expected) in [
+            (&[128, 128, 128][..], [128, 128, 128, 255]),
+            (&[76, 85, 255][..], [254, 0, 0, 255]),
+            (&[255, 128, 128, 0][..], [0, 0, 0, 0]),
+        ] {
+            let pixel = decode_jp2_pixel(OPJ_CLRSPC_SYCC, samples).unwrap();
+            assert!(
+                (0..4).all(|channel| pixel[channel].abs_diff(expected[channel]) <= 1),
+                "sYCC {:?} gave {:?}, expected {:?}",
+                samples,
+                pixel,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn cmyk_jp2_is_converted_to_rgb() {
+        use openjp2::openjpeg::COLOR_SPACE::OPJ_CLRSPC_CMYK;
+
+        for (samples, expected) in [
+            ([0, 0, 0, 0], [255, 255, 255, 255]),
+            ([0, 0, 0, 255], [0, 0, 0, 255]),
+            ([255, 0, 0, 0], [0, 255, 255, 255]),
+            ([0, 255, 255, 51], [204, 0, 0, 255]),
+        ] {
+            let pixel = decode_jp2_pixel(OPJ_CLRSPC_CMYK, &samples).unwrap();
+            assert_eq!(pixel, expected, "CMYK {:?}", samples);
+        }
+
+        // CMYK needs all four inks
+        assert!(matches!(
+            decode_jp2_pixel(OPJ_CLRSPC_CMYK, &[0, 0, 0]),
+            Err(DecodeError::Corrupt(_))
+        ));
+    }
+
+    #[test]
+    fn eycc_jp2_is_unsupported() {
+        use openjp2::openjpeg::COLOR_SPACE::OPJ_CLRSPC_EYCC;
+
+        assert!(matches!(
+            decode_jp2_pixel(OPJ_CLRSPC_EYCC, &[128, 128, 128]),
+            Err(DecodeError::UnsupportedFormat(_))
+        ));
+    }
+
+    #[test]
+    fn view_state_round_trips() {
+        let view_state = ViewState {
+            zoom_level: 2.5,
+            view_x: -13.25,
+            view_y: 400.0,
+        };
+        let stored: std::collections::HashMap<_, _> = view_state.fields().into_iter().collect();
+        let field = |key: &str| stored.get(key).copied();
+        assert_eq!(ViewState::from_fields(field), Some(view_state));
+
+        // An entry missing a number isn't restored
+        let partial = |key: &str| field(key).filter(|_| key != VIEW_STATE_VIEW_Y);
+        assert_eq!(ViewState::from_fields(partial), None);
+    }
+
+    #[test]
+    fn stored_view_states_keep_the_newest_files() {
+        // A suite of its own so the test never touches the app's defaults
+        let suite = NSString::from_str("jp2view.tests.view-states");
+        let defaults =
+            unsafe { NSUserDefaults::initWithSuiteName(NSUserDefaults::alloc(), Some(&suite)) }
+                .expect("test defaults suite");
+        unsafe { defaults.removePersistentDomainForName(&suite) };
+
+        let view_state = |index: usize| ViewState {
+            zoom_level: 2.0,
+            view_x: index as f64,
+            view_y: 10.0,
+        };
+        let path = |index: usize| format!("/images/scan-{}.jp2", index);
+        let count = MAX_FILE_VIEW_STATES + 10;
+        for index in 0..count {
+            let file_path = NSString::from_str(&path(index));
+            store_view_state(&defaults, &file_path, view_state(index), index as f64);
+        }
+
+        let stored = file_view_states(&defaults).expect("view states stored");
+        assert_eq!(stored.count(), MAX_FILE_VIEW_STATES);
+        for index in 0..count {
+            let expected = (index >= count - MAX_FILE_VIEW_STATES).then(|| view_state(index));
+            assert_eq!(
+                saved_view_state(&defaults, &path(index)),
+                expected,
+                "file {}",
+                index
+            );
+        }
+
+        unsafe { defaults.removePersistentDomainForName(&suite) };
+    }
+
+    // A zoom after Fit scales about the origin, not a point anchored before it
+    #[test]
+    fn fit_to_view_clears_zoom_anchor() {
+        let mut state = AppState {
+            source_width: 800,
+            source_height: 600,
+            viewport_size: (400.0, 300.0),
+            zoom_anchor: Some((700.0, 500.0)),
+            ..AppState::default()
+        };
+        state.fit_to_view(400.0, 300.0);
+        assert_eq!(state.zoom_anchor, None);
+
+        state.set_zoom(1.0);
+        assert_eq!((state.view_x, state.view_y), (0.0, 0.0));
+    }
+
+    #[test]
+    fn set_pan_keeps_source_on_screen() {
+        // A source smaller than the view stays at the origin
+        for (zoom, view_x, view_y, expected) in [
+            (1.0, -50.0, -50.0, (0.0, 0.0)),
+            (1.0, 5000.0, 5000.0, (1520.0, 880.0)),
+            (2.0, 100.0, 3000.0, (100.0, 1960.0)),
+            (0.125, 30.0, 30.0, (0.0, 0.0)),
+        ] {
+            let mut state = AppState {
+                source_width: 1920,
+                source_height: 1080,
+                zoom_level: zoom,
+                viewport_size: (400.0, 200.0),
+                ..AppState::default()
+            };
+            state.set_pan(view_x, view_y);
+            assert_eq!((state.view_x, state.view_y), expected, "zoom {}", zoom);
+        }
+    }
+
+    // At 3x with a pan of 4 viewport pixels, the first source column is cut
+    // to 2 pixels unless the pan snaps, when every column is 3 pixels wide
+    #[test]
+    fn snapped_pan_draws_whole_source_pixels() {
+        let width = 8;
+        let buffer = (0..width)
+            .flat_map(|x| {
+                if x % 2 == 0 {
+                    [0, 0, 0, 255]
+                } else {
+                    [255, 255, 255, 255]
+                }
+            })
+            .collect();
+        let pattern = SourcePattern {
+            buffer,
+            width,
+            height: 1,
+            bytes_per_row: width * 4,
+        };
+        let column_widths = |snap_pan_to_pixels| {
+            let state = AppState {
+                zoom_level: 3.0,
+                view_x: 4.0,
+                snap_pan_to_pixels,
+                ..AppState::default()
+            };
+            let (view_x, view_y) = state.render_origin();
+            let pixels = render_region(&pattern, 3.0, view_x, view_y, 12, 1);
+            let mut widths = Vec::new();
+            for (index, pixel) in pixels.chunks_exact(4).enumerate() {
+                if index == 0 || pixel[0] != pixels[(index - 1) * 4] {
+                    widths.push(0);
+                }
+                *widths.last_mut().unwrap() += 1;
+            }
+            widths
+        };
+
+        assert_eq!(column_widths(false), [2, 3, 3, 3, 1]);
+        assert_eq!(column_widths(true), [3, 3, 3, 3]);
+    }
+
+    // Zooming about a point keeps the source pixel drawn there in place.
+    // Where that would pan past the source the pan is clamped instead, and
+    // what's drawn at the anchor still matches the state.
+    #[test]
+    fn zoom_about_point_keeps_anchor() {
+        let (width, height) = (200, 200);
+        let buffer = (0..height)
+            .flat_map(|y| (0..width).flat_map(move |x| [x as u8, y as u8, 0, 255]))
+            .collect();
+        let pattern = SourcePattern {
+            buffer,
+            width,
+            height,
+            bytes_per_row: width * 4,
+        };
+        let drawn_at = |state: &AppState, (x, y): (usize, usize)| {
+            let (view_x, view_y) = state.render_origin();
+            let pixels = render_region(&pattern, state.zoom_level, view_x, view_y, 100, 80);
+            let index = (y * 100 + x) * 4;
+            (pixels[index], pixels[index + 1])
+        };
+
+        for (zoom, anchor, held) in [
+            (3.0, (50, 40), (true, true)),
+            (1.25, (10, 70), (true, true)),
+            (0.75, (90, 70), (true, false)),
+            (0.25, (30, 30), (false, false)),
+        ] {
+            let mut state = AppState {
+                source_width: width,
+                source_height: height,
+                viewport_size: (100.0, 80.0),
+                zoom_level: 2.0,
+                view_x: 151.0,
+                view_y: 101.0,
+                ..AppState::default()
+            };
+            let before = drawn_at(&state, anchor);
+            state.set_zoom_about_point(zoom, anchor.0 as f64, anchor.1 as f64);
+            let after = drawn_at(&state, anchor);
+
+            assert!(state.view_x >= 0.0 && state.view_y >= 0.0, "zoom {}", zoom);
+            let source = (
+                ((state.view_x + anchor.0 as f64) / state.zoom_level) as u8,
+                ((state.view_y + anchor.1 as f64) / state.zoom_level) as u8,
+            );
+            assert_eq!(after, source, "zoom {}", zoom);
+            assert_eq!(
+                (after.0 == before.0, after.1 == before.1),
+                held,
+                "zoom {}",
+                zoom
+            );
+        }
+    }
+
+    #[test]
+    fn solid_pattern_has_zero_sharpness() {
+        let custom: [Rc<dyn PatternGenerator>; 1] = [Rc::new(SolidPattern([40, 80, 120, 255]))];
+        let state = AppState {
+            pattern_type: PatternType::Custom(0),
+            source_width: 64,
+            source_height: 48,
+            ..AppState::default()
+        };
+        let pattern = generate_source_pattern(&state, &custom);
+        assert_eq!(pattern.sharpness(), 0.0);
+    }
+
+    // Far enough out that a fixed grid would step over source pixels, a
+    // single white pixel still lightens the output pixel covering it
+    #[test]
+    fn supersampling_covers_every_source_pixel() {
+        let (width, zoom) = (64, 1.0 / 16.0);
+        for white in 0..width {
+            let mut buffer = [0, 0, 0, 255].repeat(width);
+            buffer[white * 4..white * 4 + 3].copy_from_slice(&[255, 255, 255]);
+            let pattern = SourcePattern {
+                buffer,
+                width,
+                height: 1,
+                bytes_per_row: width * 4,
+            };
+
+            let pixels = render_region_supersampled(&pattern, zoom, 0.0, 0.0, 4, 1);
+            let covering = white / 16;
+            assert!(
+                pixels[covering * 4] > 0,
+                "source pixel {} was skipped",
+                white
+            );
+        }
+    }
+
+    // Every pattern renders at awkward sizes, zooms and pans through every
+    // sampler and overlay without panicking
+    #[test]
+    fn patterns_render_at_edge_case_sizes() {
+        let custom: [Rc<dyn PatternGenerator>; 1] = [Rc::new(SolidPattern([40, 80, 120, 255]))];
+
+        // A small decoded image, drawn into every source size
+        let image = PatternType::Image {
+            rgba: (0..5 * 3 * 4).map(|i| (i * 17) as u8).collect(),
+            width: 5,
+            height: 3,
+        };
+
+        for pattern_type in PatternType::ALL
+            .into_iter()
+            .chain([PatternType::Custom(0), image])
+        {
+            for (width, height) in SELFTEST_SIZES {
+                for secondary_text in SECONDARY_TEXTS {
+                    let state = AppState {
+                        pattern_type: pattern_type.clone(),
+                        source_width: width,
+                        source_height: height,
+                        secondary_text: secondary_text.map(str::to_string),
+                        chroma_key_enabled: true,
+                        ..AppState::default()
+                    };
+                    let pattern = generate_source_pattern(&state, &custom);
+                    pattern.sharpness();
+                    pattern.sharpest_region(4);
+
+                    for zoom_level in SELFTEST_ZOOM_LEVELS {
+                        for (view_x, view_y) in SELFTEST_PANS {
+                            let out_width = (width as f64 * zoom_level) as usize;
+                            let out_height = (height as f64 * zoom_level) as usize;
+                            let mut pixels = render_region(
+                                &pattern, zoom_level, view_x, view_y, out_width, out_height,
+                            );
+                            apply_color_filters(&mut pixels, &state.active_filters());
+                            apply_rulers(
+                                &mut pixels,
+                                out_width,
+                                out_height,
+                                zoom_level,
+                                view_x,
+                                view_y,
+                            );
+                            render_region_supersampled(
+                                &pattern, zoom_level, view_x, view_y, out_width, out_height,
+                            );
+                            render_region_bilinear(
+                                &pattern, zoom_level, view_x, view_y, out_width, out_height,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // A render pinned to the hash of its output, so sampling or filter changes
+    // that alter pixels show up in the tests. Text cases use GOLDEN_FILE_NAME
+    // as the secondary text; every other setting is the AppState default. Only
+    // update a hash after confirming the new output is intended.
+    struct GoldenRender {
+        pattern_type: PatternType,
+        size: (usize, usize),
+        zoom_level: f64,
+        view: (f64, f64),
+        options: RenderOptions,
+        filters: &'static [ColorFilter],
+        hash: u64,
+    }
+
+    const GOLDEN_RENDERS: [GoldenRender; 9] = [
+        GoldenRender {
+            pattern_type: PatternType::Checkerboard,
+            size: (64, 48),
+            zoom_level: 1.0,
+            view: (0.0, 0.0),
+            options: RenderOptions {
+                debug_borders: false,
+                debug_border_inset: 0,
+                blend_debug_corners: false,
+                borders_only: false,
+                rulers: false,
+                sampling: SamplingMode::Nearest,
+            },
+            filters: &[],
+            hash: 0xe7065812064c9825,
+        },
+        GoldenRender {
+            pattern_type: PatternType::Checkerboard,
+            size: (64, 48),
+            zoom_level: 2.5,
+            view: (13.5, 7.25),
+            options: RenderOptions {
+                debug_borders: false,
+                debug_border_inset: 0,
+                blend_debug_corners: false,
+                borders_only: false,
+                rulers: false,
+                sampling: SamplingMode::Supersampled,
+            },
+            filters: &[],
+            hash: 0x80d4542253b7909c,
+        },
+        GoldenRender {
+            pattern_type: PatternType::Gradient,
+            size: (100, 80),
+            zoom_level: 0.5,
+            view: (0.0, 0.0),
+            options: RenderOptions {
+                debug_borders: true,
+                debug_border_inset: 2,
+                blend_debug_corners: false,
+                borders_only: false,
+                rulers: false,
+                sampling: SamplingMode::Nearest,
+            },
+            filters: &[],
+            hash: 0xb9131c1f32bf6c2f,
+        },
+        GoldenRender {
+            pattern_type: PatternType::Gradient,
+            size: (100, 80),
+            zoom_level: 3.0,
+            view: (40.0, 20.0),
+            options: RenderOptions {
+                debug_borders: true,
+                debug_border_inset: 0,
+                blend_debug_corners: false,
+                borders_only: true,
+                rulers: false,
+                sampling: SamplingMode::Nearest,
+            },
+            filters: &[],
+            hash: 0xc252bbe54c80889d,
+        },
+        GoldenRender {
+            pattern_type: PatternType::Text,
+            size: (320, 240),
+            zoom_level: 1.0,
+            view: (0.0, 0.0),
+            options: RenderOptions {
+                debug_borders: true,
+                debug_border_inset: 0,
+                blend_debug_corners: false,
+                borders_only: false,
+                rulers: false,
+                sampling: SamplingMode::Nearest,
+            },
+            filters: &[ColorFilter::ChromaKey {
+                color: [255, 255, 255],
+                tolerance: 10,
+            }],
+            hash: 0x5d1b439df596eaf9,
+        },
+        GoldenRender {
+            pattern_type: PatternType::Text,
+            size: (320, 240),
+            zoom_level: 1.5,
+            view: (60.0, 30.0),
+            options: RenderOptions {
+                debug_borders: false,
+                debug_border_inset: 0,
+                blend_debug_corners: false,
+                borders_only: false,
+                rulers: false,
+                sampling: SamplingMode::Supersampled,
+            },
+            filters: &[],
+            hash: 0xd603b2f9ac93c670,
+        },
+        GoldenRender {
+            pattern_type: PatternType::Gradient,
+            size: (100, 80),
+            zoom_level: 2.0,
+            view: (0.0, 0.0),
+            options: RenderOptions {
+                debug_borders: true,
+                debug_border_inset: 1,
+                blend_debug_corners: true,
+                borders_only: false,
+                rulers: false,
+                sampling: SamplingMode::Nearest,
+            },
+            filters: &[],
+            hash: 0x765e57f80ed35eb5,
+        },
+        GoldenRender {
+            pattern_type: PatternType::Checkerboard,
+            size: (200, 150),
+            zoom_level: 1.5,
+            view: (30.0, 45.0),
+            options: RenderOptions {
+                debug_borders: false,
+                debug_border_inset: 0,
+                blend_debug_corners: false,
+                borders_only: false,
+                rulers: true,
+                sampling: SamplingMode::Nearest,
+            },
+            filters: &[],
+            hash: 0x3ab0fc2779638a3d,
+        },
+        GoldenRender {
+            pattern_type: PatternType::Gradient,
+            size: (100, 80),
+            zoom_level: 1.0,
+            view: (0.0, 0.0),
+            options: RenderOptions {
+                debug_borders: false,
+                debug_border_inset: 0,
+                blend_debug_corners: false,
+                borders_only: false,
+                rulers: false,
+                sampling: SamplingMode::Nearest,
+            },
+            filters: &[ColorFilter::ColorBlind(ColorBlindType::Deuteranopia)],
+            hash: 0xe04c46e20747a60f,
+        },
+    ];
+
+    // 64-bit FNV-1a, stable across runs and platforms
+    fn fnv1a_64(bytes: &[u8]) -> u64 {
+        bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+
+    #[test]
+    fn golden_renders_match() {
+        for (index, golden) in GOLDEN_RENDERS.iter().enumerate() {
+            let state = AppState {
+                pattern_type: golden.pattern_type.clone(),
+                source_width: golden.size.0,
+                source_height: golden.size.1,
+                secondary_text: Some(GOLDEN_FILE_NAME.to_string()),
+                ..AppState::default()
+            };
+            let pattern = generate_source_pattern(&state, &[]);
+            let pixels = render_pixels(
+                &pattern,
+                golden.zoom_level,
+                golden.view.0,
+                golden.view.1,
+                scaled_source_size(&pattern, golden.zoom_level),
+                golden.options,
+                golden.filters,
+            );
+
+            let hash = fnv1a_64(&pixels);
+            assert_eq!(
+                hash, golden.hash,
+                "golden render {} ({:?}) hashed {:#018x}, expected {:#018x}",
+                index, golden.pattern_type, hash, golden.hash
+            );
+        }
+    }
+}